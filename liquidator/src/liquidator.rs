@@ -1,36 +1,41 @@
 use std::collections::HashMap;
 
 use anchor_client::{
-    solana_client::rpc_client::RpcClient,
+    solana_client::{
+        rpc_client::RpcClient,
+        rpc_config::{RpcSimulateTransactionAccountsConfig, RpcSimulateTransactionConfig},
+    },
     solana_sdk::{
         borsh::try_from_slice_unchecked,
         commitment_config::CommitmentConfig,
+        message::Message,
         pubkey::Pubkey,
         signature::{Keypair, Signer},
-        stake,
+        stake::{self, state::StakeState},
         stake_history::StakeHistory,
         system_program,
         sysvar::{clock, SysvarId},
         transaction::Transaction,
     },
-    Client, ClientError, Program,
+    Client, ClientError, Program, RequestBuilder,
 };
 use anchor_lang::prelude::AccountMeta;
 use anchor_spl::{associated_token::get_associated_token_address, token::spl_token};
 use log::{error, info, warn};
 use omnisol::{
     id,
-    state::{Collateral, Oracle, Pool, User, WithdrawInfo},
+    state::{Collateral, LiquidationRoute, Oracle, Pool, User, WithdrawInfo, WithdrawMode},
 };
 use spl_stake_pool::{
-    find_stake_program_address, find_withdraw_authority_program_address,
+    find_deposit_authority_program_address, find_stake_program_address, find_withdraw_authority_program_address,
     state::{StakePool, ValidatorList},
     ID,
 };
 
 use crate::utils::{
     get_collateral_data, get_liquidator, get_oracle, get_oracle_data, get_pool_authority, get_pool_data,
-    get_stake_account_record, get_token_whitelist, get_user, get_user_data, get_whitelisted_token_data,
+    get_stake_account_record, get_token_whitelist, get_treasury, get_user, get_user_data, get_validator,
+    get_whitelisted_token_data,
 };
 
 use crate::Args;
@@ -74,6 +79,7 @@ impl<'a> Liquidator<'a> {
             oracle_data: Oracle {
                 authority: Default::default(),
                 priority_queue: vec![],
+                staging_queue: vec![],
             },
             pool_data: Default::default(),
             collateral_data: Default::default(),
@@ -94,7 +100,7 @@ impl<'a> Liquidator<'a> {
 
         self.user_data = user_data.clone();
 
-        self.user_key = get_user(withdraw_info.authority);
+        self.user_key = get_user(withdraw_info.pool, withdraw_info.authority);
         let user = match user_data.get(&self.user_key) {
             None => {
                 error!("Can't find user account");
@@ -137,6 +143,16 @@ impl<'a> Liquidator<'a> {
 
         self.withdraw_address = withdraw_address;
 
+        if withdraw_info.mode == WithdrawMode::Delayed {
+            self.process_delayed_withdrawal(user, &withdraw_info);
+            return;
+        }
+
+        if withdraw_info.authority == self.liquidator_wallet {
+            warn!("Liquidator can't liquidate for its own withdraw request");
+            return;
+        }
+
         // liquidate collaterals while withdraw request won't be processed
         while self.amount_to_liquidate > 0 {
             self.liquidate(user);
@@ -170,6 +186,11 @@ impl<'a> Liquidator<'a> {
                 continue;
             };
 
+            if collateral.pending_vote_account.is_some() {
+                warn!("Collateral {} is being redelegated", queue_member.collateral);
+                continue;
+            }
+
             // find pool_authority
             let pool_authority = get_pool_authority(collateral.pool);
 
@@ -191,9 +212,25 @@ impl<'a> Liquidator<'a> {
             // create additional_keypair for split stake account
             let additional_signer = Keypair::new();
 
-            // get some remaining accounts
-            let (stake_account_record, remaining_accounts) = match self
-                .get_remaining_accounts(
+            let amount = if self.amount_to_liquidate >= queue_member.amount {
+                queue_member.amount
+            } else {
+                self.amount_to_liquidate
+            };
+
+            // native stake can be liquidated via the stake pool as well, if it's set
+            let routes = if collateral.is_native && self.args.stake_pool_mint.is_some() {
+                vec![LiquidationRoute::UnstakeIt, LiquidationRoute::StakePool]
+            } else {
+                vec![LiquidationRoute::UnstakeIt]
+            };
+
+            let mut requests = vec![];
+
+            for route in routes {
+                // get some remaining accounts
+                let (stake_account_record, remaining_accounts) = match self.get_remaining_accounts(
+                    route,
                     additional_signer.pubkey(),
                     collateral,
                     source_stake,
@@ -201,51 +238,72 @@ impl<'a> Liquidator<'a> {
                     queue_member.amount,
                     pool_authority,
                 ) {
-                Ok((stake_account_record, remaining_accounts)) => (stake_account_record, remaining_accounts),
-                Err(e) => {
-                    error!("Can't get remaining accounts: {}", e);
-                    continue;
-                }
-            };
+                    Ok((stake_account_record, remaining_accounts)) => (stake_account_record, remaining_accounts),
+                    Err(e) => {
+                        error!("Can't get remaining accounts for {:?} route: {}", route, e);
+                        continue;
+                    }
+                };
+
+                let request = self
+                    .program
+                    .request()
+                    .accounts(omnisol::accounts::LiquidateCollateral {
+                        pool: collateral.pool,
+                        pool_authority,
+                        collateral: queue_member.collateral,
+                        collateral_owner: collateral.user,
+                        collateral_owner_wallet: collateral_owner.wallet,
+                        user_wallet: user.wallet,
+                        user: self.user_key,
+                        withdraw_info: self.withdraw_address,
+                        treasury: get_treasury(user.pool),
+                        oracle: self.oracle,
+                        source_stake,
+                        validator: self.get_collateral_validator(collateral),
+                        liquidator: self.liquidator,
+                        pool_account: self.args.pool,
+                        sol_reserves: self.args.reserves,
+                        protocol_fee: self.args.fee_protocol,
+                        protocol_fee_destination: self.args.destination_fee,
+                        fee_account: self.args.account_fee,
+                        stake_account_record,
+                        unstake_it_program: self.args.unstake_it,
+                        authority: self.liquidator_wallet,
+                        clock: clock::id(),
+                        token_program: spl_token::id(),
+                        stake_program: stake::program::id(),
+                        system_program: system_program::id(),
+                    })
+                    .accounts(remaining_accounts)
+                    .args(omnisol::instruction::LiquidateCollateral { amount, route })
+                    .signer(&additional_signer);
+
+                requests.push((route, request));
+            }
 
-            let amount = if self.amount_to_liquidate >= queue_member.amount {
-                queue_member.amount
+            // send tx to contract via the route that gives more lamports to the user
+            let request = if requests.len() > 1 {
+                requests
+                    .into_iter()
+                    .filter_map(|(route, request)| {
+                        let lamports = self.simulate_lamports_out(&request, user.wallet)?;
+                        info!("Liquidation via {:?} route gives {} lamports", route, lamports);
+                        Some((lamports, request))
+                    })
+                    .max_by_key(|(lamports, _)| *lamports)
+                    .map(|(_, request)| request)
             } else {
-                self.amount_to_liquidate
+                requests.pop().map(|(_, request)| request)
             };
 
-            // send tx to contract
-            let request = self
-                .program
-                .request()
-                .accounts(omnisol::accounts::LiquidateCollateral {
-                    pool: collateral.pool,
-                    pool_authority,
-                    collateral: queue_member.collateral,
-                    collateral_owner: collateral.user,
-                    collateral_owner_wallet: collateral_owner.wallet,
-                    user_wallet: user.wallet,
-                    user: self.user_key,
-                    withdraw_info: self.withdraw_address,
-                    oracle: self.oracle,
-                    source_stake,
-                    liquidator: self.liquidator,
-                    pool_account: self.args.pool,
-                    sol_reserves: self.args.reserves,
-                    protocol_fee: self.args.fee_protocol,
-                    protocol_fee_destination: self.args.destination_fee,
-                    fee_account: self.args.account_fee,
-                    stake_account_record,
-                    unstake_it_program: self.args.unstake_it,
-                    authority: self.liquidator_wallet,
-                    clock: clock::id(),
-                    token_program: spl_token::id(),
-                    stake_program: stake::program::id(),
-                    system_program: system_program::id(),
-                })
-                .accounts(remaining_accounts)
-                .args(omnisol::instruction::LiquidateCollateral { amount })
-                .signer(&additional_signer);
+            let request = match request {
+                None => {
+                    error!("Can't build liquidation request");
+                    continue;
+                }
+                Some(request) => request,
+            };
 
             let instructions = request.instructions().expect("");
 
@@ -275,8 +333,155 @@ impl<'a> Liquidator<'a> {
         }
     }
 
+    /// Claims the delayed withdraw request if its stake is deactivated,
+    /// otherwise deactivates native collateral from the priority queue that covers the whole request.
+    pub fn process_delayed_withdrawal(&self, user: &User, withdraw_info: &WithdrawInfo) {
+        let pool_authority = get_pool_authority(withdraw_info.pool);
+
+        if withdraw_info.stake_account != Pubkey::default() {
+            let epoch = match self.program.rpc().get_epoch_info() {
+                Ok(epoch_info) => epoch_info.epoch,
+                Err(e) => {
+                    error!("Can't get epoch info: {}", e);
+                    return;
+                }
+            };
+
+            if epoch < withdraw_info.claimable_epoch {
+                info!("Withdrawal is claimable since epoch {}", withdraw_info.claimable_epoch);
+                return;
+            }
+
+            let result = self
+                .program
+                .request()
+                .accounts(omnisol::accounts::ClaimWithdrawal {
+                    pool: withdraw_info.pool,
+                    pool_authority,
+                    user_wallet: user.wallet,
+                    user: self.user_key,
+                    withdraw_info: self.withdraw_address,
+                    collateral_owner_wallet: withdraw_info.collateral_owner_wallet,
+                    stake_account: withdraw_info.stake_account,
+                    clock: clock::id(),
+                    stake_history: StakeHistory::id(),
+                    stake_program: stake::program::id(),
+                })
+                .args(omnisol::instruction::ClaimWithdrawal {})
+                .send();
+
+            match result {
+                Ok(signature) => info!("Claimed withdrawal with signature: {}", signature),
+                Err(e) => error!("Claim failed with an error - {}", e),
+            }
+            return;
+        }
+
+        // the whole request is deactivated from one native collateral of the request pool
+        for queue_member in &self.oracle_data.priority_queue {
+            let collateral = match self.collateral_data.get(&queue_member.collateral) {
+                Some(collateral)
+                    if collateral.is_native
+                        && collateral.pool == withdraw_info.pool
+                        && collateral.pending_vote_account.is_none()
+                        && queue_member.amount >= withdraw_info.amount =>
+                {
+                    collateral
+                }
+                _ => continue,
+            };
+
+            match self.pool_data.get(&collateral.pool) {
+                Some(pool) if pool.is_active => {}
+                _ => continue,
+            }
+
+            let collateral_owner = match self.user_data.get(&collateral.user) {
+                None => {
+                    error!("Can't find collateral owner account");
+                    continue;
+                }
+                Some(collateral_owner) => collateral_owner,
+            };
+
+            // create additional_keypair for split stake account
+            let additional_signer = Keypair::new();
+
+            let result = self
+                .program
+                .request()
+                .accounts(omnisol::accounts::DeactivateForWithdrawal {
+                    pool: collateral.pool,
+                    pool_authority,
+                    collateral: queue_member.collateral,
+                    collateral_owner: collateral.user,
+                    collateral_owner_wallet: collateral_owner.wallet,
+                    user_wallet: user.wallet,
+                    user: self.user_key,
+                    withdraw_info: self.withdraw_address,
+                    treasury: get_treasury(collateral.pool),
+                    oracle: self.oracle,
+                    delegated_stake: collateral.delegated_stake,
+                    validator: self.get_collateral_validator(collateral),
+                    split_stake: additional_signer.pubkey(),
+                    liquidator: self.liquidator,
+                    authority: self.liquidator_wallet,
+                    clock: clock::id(),
+                    stake_program: stake::program::id(),
+                    system_program: system_program::id(),
+                })
+                .args(omnisol::instruction::DeactivateForWithdrawal {})
+                .signer(&additional_signer)
+                .send();
+
+            match result {
+                Ok(signature) => {
+                    info!("Deactivated stake for withdrawal with signature: {}", signature);
+                    return;
+                }
+                Err(e) => error!("Deactivation failed with an error - {}", e),
+            }
+        }
+
+        warn!("Can't find native collateral to deactivate for request {}", self.withdraw_address);
+    }
+
+    /// Simulates the liquidation and returns the balance of the [wallet] after it.
+    fn simulate_lamports_out(&self, request: &RequestBuilder, wallet: Pubkey) -> Option<u64> {
+        let instructions = request.instructions().ok()?;
+        let tx = Transaction::new_unsigned(Message::new(&instructions, Some(&self.liquidator_wallet)));
+
+        let config = RpcSimulateTransactionConfig {
+            sig_verify: false,
+            replace_recent_blockhash: true,
+            accounts: Some(RpcSimulateTransactionAccountsConfig {
+                encoding: None,
+                addresses: vec![wallet.to_string()],
+            }),
+            ..Default::default()
+        };
+
+        let result = match self.program.rpc().simulate_transaction_with_config(&tx, config) {
+            Ok(response) => response.value,
+            Err(e) => {
+                warn!("Can't simulate liquidation: {}", e);
+                return None;
+            }
+        };
+
+        if let Some(e) = result.err {
+            warn!("Liquidation simulation failed with an error - {}", e);
+            return None;
+        }
+
+        let account = result.accounts?.into_iter().next()??;
+
+        Some(account.lamports)
+    }
+
     fn get_remaining_accounts(
         &self,
+        route: LiquidationRoute,
         split_stake: Pubkey,
         collateral: &Collateral,
         source_stake: Pubkey,
@@ -295,11 +500,21 @@ impl<'a> Liquidator<'a> {
             let stake_account_record = get_stake_account_record(self.args.pool, stake_account, self.args.unstake_it);
 
             // get list of remaining accounts
-            let remaining_accounts = vec![AccountMeta {
+            let mut remaining_accounts = vec![AccountMeta {
                 pubkey: split_stake,
                 is_signer: true,
                 is_writable: true,
             }];
+
+            if route == LiquidationRoute::StakePool {
+                let stake_pool_mint = self.args.stake_pool_mint.ok_or(ClientError::AccountNotFound)?;
+                remaining_accounts.extend(self.get_stake_pool_deposit_accounts(
+                    stake_pool_mint,
+                    source_stake,
+                    pool_authority,
+                )?);
+            }
+
             Ok((stake_account_record, remaining_accounts))
         } else {
             let stake_account_record = get_stake_account_record(self.args.pool, split_stake, self.args.unstake_it);
@@ -388,8 +603,126 @@ impl<'a> Liquidator<'a> {
                     is_signer: false,
                     is_writable: true,
                 },
+                AccountMeta {
+                    pubkey: token_whitelist,
+                    is_signer: false,
+                    is_writable: false,
+                },
             ];
             Ok((stake_account_record, remaining_accounts))
         }
     }
+
+    /// Returns the validator entry of the native collateral's vote account.
+    /// LP token collateral has no validator entry, so any address is accepted.
+    fn get_collateral_validator(&self, collateral: &Collateral) -> Pubkey {
+        let vote_account = self
+            .program
+            .rpc()
+            .get_account_data(&collateral.delegated_stake)
+            .ok()
+            .and_then(|data| try_from_slice_unchecked::<StakeState>(data.as_slice()).ok())
+            .and_then(|stake_state| stake_state.delegation())
+            .map(|delegation| delegation.voter_pubkey)
+            .unwrap_or_default();
+
+        get_validator(collateral.pool, vote_account)
+    }
+
+    /// Returns remaining accounts to deposit native stake into the whitelisted stake pool and withdraw SOL from it.
+    fn get_stake_pool_deposit_accounts(
+        &self,
+        stake_pool_mint: Pubkey,
+        stake_account: Pubkey,
+        pool_authority: Pubkey,
+    ) -> Result<Vec<AccountMeta>, ClientError> {
+        let token_whitelist = get_token_whitelist(stake_pool_mint);
+        let whitelisted_token_data = get_whitelisted_token_data(&self.program, token_whitelist)?;
+        let stake_pool_program = whitelisted_token_data.pool_program;
+
+        let stake_pool_data = self.program.rpc().get_account_data(&whitelisted_token_data.pool)?;
+        let stake_pool = try_from_slice_unchecked::<StakePool>(stake_pool_data.as_slice())
+            .map_err(|_| ClientError::AccountNotFound)?;
+
+        // the stake is deposited to the validator stake account of its vote account
+        let stake_account_data = self.program.rpc().get_account_data(&stake_account)?;
+        let delegation = try_from_slice_unchecked::<StakeState>(stake_account_data.as_slice())
+            .ok()
+            .and_then(|stake_state| stake_state.delegation())
+            .ok_or(ClientError::AccountNotFound)?;
+
+        let (validator_stake_account, _) = find_stake_program_address(
+            &stake_pool_program,
+            &delegation.voter_pubkey,
+            &whitelisted_token_data.pool,
+        );
+        let (stake_pool_deposit_authority, _) =
+            find_deposit_authority_program_address(&stake_pool_program, &whitelisted_token_data.pool);
+        let (stake_pool_withdraw_authority, _) =
+            find_withdraw_authority_program_address(&stake_pool_program, &whitelisted_token_data.pool);
+        let pool_token_account = get_associated_token_address(&pool_authority, &whitelisted_token_data.mint);
+
+        Ok(vec![
+            AccountMeta {
+                pubkey: token_whitelist,
+                is_signer: false,
+                is_writable: false,
+            },
+            AccountMeta {
+                pubkey: stake_pool_program,
+                is_signer: false,
+                is_writable: false,
+            },
+            AccountMeta {
+                pubkey: whitelisted_token_data.pool,
+                is_signer: false,
+                is_writable: true,
+            },
+            AccountMeta {
+                pubkey: stake_pool.validator_list,
+                is_signer: false,
+                is_writable: true,
+            },
+            AccountMeta {
+                pubkey: stake_pool_deposit_authority,
+                is_signer: false,
+                is_writable: false,
+            },
+            AccountMeta {
+                pubkey: stake_pool_withdraw_authority,
+                is_signer: false,
+                is_writable: false,
+            },
+            AccountMeta {
+                pubkey: validator_stake_account,
+                is_signer: false,
+                is_writable: true,
+            },
+            AccountMeta {
+                pubkey: stake_pool.reserve_stake,
+                is_signer: false,
+                is_writable: true,
+            },
+            AccountMeta {
+                pubkey: stake_pool.manager_fee_account,
+                is_signer: false,
+                is_writable: true,
+            },
+            AccountMeta {
+                pubkey: whitelisted_token_data.mint,
+                is_signer: false,
+                is_writable: true,
+            },
+            AccountMeta {
+                pubkey: pool_token_account,
+                is_signer: false,
+                is_writable: true,
+            },
+            AccountMeta {
+                pubkey: StakeHistory::id(),
+                is_signer: false,
+                is_writable: false,
+            },
+        ])
+    }
 }
@@ -67,6 +67,10 @@ pub struct Args {
     /// Fee account address
     #[arg(short, long, value_name = "FEE_ACCOUNT", env = "FEE_ACCOUNT")]
     pub account_fee: Pubkey,
+
+    /// LP token mint of the whitelisted stake pool to liquidate native stake via it, if it's cheaper
+    #[arg(long, value_name = "STAKE_POOL_MINT", env = "STAKE_POOL_MINT")]
+    pub stake_pool_mint: Option<Pubkey>,
 }
 
 fn main() {
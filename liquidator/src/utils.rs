@@ -8,7 +8,7 @@ use anchor_client::{
 use gimli::ReaderOffset;
 use omnisol::{
     id,
-    state::{Collateral, Liquidator, Oracle, Pool, User, Whitelist, WithdrawInfo},
+    state::{Collateral, Liquidator, Oracle, Pool, Treasury, User, Validator, Whitelist, WithdrawInfo},
 };
 
 pub const WITHDRAW_INFO_DISCRIMINATOR: [u8; 8] = [103, 244, 107, 42, 135, 228, 81, 107];
@@ -36,8 +36,16 @@ pub fn get_oracle() -> Pubkey {
     Pubkey::find_program_address(&[Oracle::SEED], &id()).0
 }
 
-pub fn get_user(user_wallet: Pubkey) -> Pubkey {
-    Pubkey::find_program_address(&[User::SEED, user_wallet.as_ref()], &id()).0
+pub fn get_treasury(pool: Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[Treasury::SEED, pool.as_ref()], &id()).0
+}
+
+pub fn get_validator(pool: Pubkey, vote_account: Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[Validator::SEED, pool.as_ref(), vote_account.as_ref()], &id()).0
+}
+
+pub fn get_user(pool: Pubkey, user_wallet: Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[User::SEED, pool.as_ref(), user_wallet.as_ref()], &id()).0
 }
 
 pub fn get_withdraw_info_list(program: &Program) -> Result<Vec<(Pubkey, WithdrawInfo)>, ClientError> {
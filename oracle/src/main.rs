@@ -1,6 +1,6 @@
 mod utils;
 
-use std::{collections::HashMap, num::ParseIntError, path::PathBuf, rc::Rc, thread, time::Duration};
+use std::{num::ParseIntError, path::PathBuf, rc::Rc, thread, time::Duration};
 
 use anchor_client::{solana_sdk::{
     commitment_config::CommitmentConfig,
@@ -9,10 +9,16 @@ use anchor_client::{solana_sdk::{
 }, Client, Cluster};
 use clap::Parser;
 use log::{info, error, LevelFilter};
-use omnisol::{id, state::Oracle};
+use omnisol::{
+    id,
+    state::{Oracle, QueueMember},
+};
 use simplelog::{ColorChoice, Config, TermLogger, TerminalMode};
 
-use crate::utils::{generate_priority_queue, get_collateral_data, get_oracle_address, get_pool_data, get_user_data};
+use crate::utils::{
+    generate_priority_queue, get_collateral_data, get_oracle_address, get_oracle_data, get_pool_data, get_stake_pool_data,
+    get_user_data, is_same_queue,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -64,8 +70,6 @@ fn main() {
     // get program public key
     let program = client.program(id());
 
-    let mut previous_queue = HashMap::new();
-
     // find oracle PDA
     let oracle = get_oracle_address();
 
@@ -96,50 +100,42 @@ fn main() {
                 continue;
             }
         };
+        let stake_pools = match get_stake_pool_data(&program) {
+            Ok(stake_pools) => stake_pools,
+            Err(e) => {
+                error!("Can't get stake pool accounts: {}", e);
+                continue;
+            }
+        };
 
         // find collaterals by user list and make priority queue
-        let queue = generate_priority_queue(user_data, collateral_data, pool_data);
+        let queue = generate_priority_queue(user_data, collateral_data, pool_data, &stake_pools);
         info!("Generated priority queue: {:?}", queue);
 
-        if previous_queue == queue {
+        // the program keeps the queue up to date by itself, so rewrite it only if it diverged
+        let oracle_data = match get_oracle_data(&program, oracle) {
+            Ok(oracle_data) => oracle_data,
+            Err(e) => {
+                error!("Can't get oracle account: {}", e);
+                continue;
+            }
+        };
+
+        if is_same_queue(&oracle_data.priority_queue, &queue) {
             info!("No changes in priority queue");
             continue;
         }
 
-        let (mut addresses, mut values) =
-            queue
-                .clone()
-                .into_iter()
-                .fold((vec![], vec![]), |(mut addresses, mut values), (a, v)| {
-                    addresses.push(a);
-                    values.push(v);
-                    (addresses, values)
-                });
-
         let mut clear = true;
-        let mut batches_amount = addresses.len() / Oracle::MAX_BATCH_LENGTH;
 
-        let last_batch_len = addresses.len() - (batches_amount * Oracle::MAX_BATCH_LENGTH);
-        if last_batch_len > 0 {
-            batches_amount += 1;
-        }
-
-        let mut address_batches = vec![];
-        let mut value_batches = vec![];
-
-        for batch_id in 0..batches_amount {
-            let batch_len = if batch_id == batches_amount - 1 {
-                last_batch_len
-            } else {
-                Oracle::MAX_BATCH_LENGTH
-            };
-            let new_addresses: Vec<_> = addresses.drain(0..batch_len).collect();
-            let new_values: Vec<_> = values.drain(0..batch_len).collect();
-            address_batches.push(new_addresses);
-            value_batches.push(new_values);
-        }
+        // an empty batch is still sent to clear the on-chain queue
+        let batches: Vec<&[QueueMember]> = if queue.is_empty() {
+            vec![&[]]
+        } else {
+            queue.chunks(Oracle::MAX_BATCH_LENGTH).collect()
+        };
 
-        for (addresses, values) in address_batches.into_iter().zip(value_batches.into_iter()) {
+        for members in batches {
             // send tx to contract
             let signature = program
                 .request()
@@ -149,8 +145,7 @@ fn main() {
                     system_program: system_program::id(),
                 })
                 .args(omnisol::instruction::UpdateOracleInfo {
-                    addresses,
-                    values,
+                    members: members.to_vec(),
                     clear,
                 })
                 .send()
@@ -160,6 +155,17 @@ fn main() {
             clear = false;
         }
 
-        previous_queue = queue;
+        // publish the staged queue at once
+        let signature = program
+            .request()
+            .accounts(omnisol::accounts::CommitOracleInfo {
+                authority: wallet_pubkey,
+                oracle,
+            })
+            .args(omnisol::instruction::CommitOracleInfo {})
+            .send()
+            .expect("Transaction failed.");
+
+        info!("Committed priority queue with signature: {}", signature);
     }
 }
@@ -2,14 +2,15 @@ use std::collections::HashMap;
 
 use anchor_client::{
     solana_client::rpc_filter::{Memcmp, MemcmpEncodedBytes, RpcFilterType},
-    solana_sdk::pubkey::Pubkey,
+    solana_sdk::{borsh::try_from_slice_unchecked, pubkey::Pubkey},
     ClientError, Program,
 };
 use gimli::ReaderOffset;
 use omnisol::{
     id,
-    state::{Collateral, Oracle, Pool, User},
+    state::{Collateral, Oracle, Pool, QueueMember, User, Whitelist},
 };
+use spl_stake_pool::state::StakePool;
 
 pub const USER_DISCRIMINATOR: [u8; 8] = [159, 117, 95, 227, 239, 151, 58, 236];
 pub const COLLATERAL_DISCRIMINATOR: [u8; 8] = [123, 130, 234, 63, 255, 240, 255, 92];
@@ -67,17 +68,54 @@ pub fn get_collateral_data(program: &Program) -> Result<Vec<(Pubkey, Collateral)
     Ok(accounts)
 }
 
+pub fn get_oracle_data(program: &Program, oracle: Pubkey) -> Result<Oracle, ClientError> {
+    program.account::<Oracle>(oracle)
+}
+
+/// Returns stake pools of the whitelisted LP tokens by their mints.
+pub fn get_stake_pool_data(program: &Program) -> Result<HashMap<Pubkey, StakePool>, ClientError> {
+    let filters = vec![RpcFilterType::DataSize(Whitelist::SIZE.into_u64())];
+    let mut stake_pools = HashMap::new();
+
+    for (_, whitelist) in program.accounts::<Whitelist>(filters)? {
+        // native stake whitelist doesn't have a stake pool
+        if whitelist.pool_program == Pubkey::default() {
+            continue;
+        }
+
+        let stake_pool_data = program.rpc().get_account_data(&whitelist.pool)?;
+        if let Ok(stake_pool) = try_from_slice_unchecked::<StakePool>(stake_pool_data.as_slice()) {
+            stake_pools.insert(whitelist.mint, stake_pool);
+        }
+    }
+
+    Ok(stake_pools)
+}
+
+/// Values [pool_tokens] in lamports by the stake pool exchange rate, rounding down, as the program does.
+fn get_lamports_value(stake_pool: &StakePool, pool_tokens: u64) -> Option<u64> {
+    if stake_pool.pool_token_supply == 0 {
+        return None;
+    }
+
+    let lamports = pool_tokens as u128 * stake_pool.total_lamports as u128 / stake_pool.pool_token_supply as u128;
+
+    u64::try_from(lamports).ok()
+}
+
+/// Queue amounts are in lamports, so LP token collaterals are valued by their stake pools.
 pub fn generate_priority_queue(
     user_data: Vec<(Pubkey, User)>,
     collateral_data: Vec<(Pubkey, Collateral)>,
     pool_data: Vec<(Pubkey, Pool)>,
-) -> HashMap<Pubkey, u64> {
-    let mut map = HashMap::new();
+    stake_pools: &HashMap<Pubkey, StakePool>,
+) -> Vec<QueueMember> {
+    let mut queue = vec![];
 
-    'outer: for (user_address, _) in user_data {
+    'outer: for (user_address, user) in user_data {
         // TODO: maybe should validate the state of user (if it blocked -> continue)
         for (address, collateral) in &collateral_data {
-            if map.len() > Oracle::MAX_PRIORITY_QUEUE_LENGTH {
+            if queue.len() >= Oracle::MAX_PRIORITY_QUEUE_LENGTH {
                 break 'outer;
             }
             if pool_data
@@ -89,14 +127,41 @@ pub fn generate_priority_queue(
             }
             if collateral.user == user_address {
                 let rest_amount = collateral.delegation_stake - collateral.liquidated_amount;
+                let rest_amount = if collateral.is_native {
+                    rest_amount
+                } else {
+                    match stake_pools
+                        .get(&collateral.stake_source)
+                        .and_then(|stake_pool| get_lamports_value(stake_pool, rest_amount))
+                    {
+                        Some(rest_value) => rest_value,
+                        None => continue,
+                    }
+                };
                 if rest_amount > 0 {
-                    map.insert(*address, rest_amount);
+                    queue.push(QueueMember {
+                        collateral: *address,
+                        user: user_address,
+                        amount: rest_amount,
+                        rate: user.rate,
+                    });
                 }
             }
         }
     }
 
-    map
+    queue
+}
+
+/// Members with equal rates can be ordered differently on-chain, so queues are compared in a canonical order.
+pub fn is_same_queue(a: &[QueueMember], b: &[QueueMember]) -> bool {
+    let canonical = |queue: &[QueueMember]| {
+        let mut queue = queue.to_vec();
+        queue.sort_by_key(|m| (m.rate, m.collateral));
+        queue
+    };
+
+    canonical(a) == canonical(b)
 }
 
 #[cfg(test)]
@@ -108,6 +173,7 @@ mod tests {
         let pubkey_1 = Pubkey::new_unique();
         let user_1 = User {
             wallet: Default::default(),
+            pool: Default::default(),
             rate: 0,
             is_blocked: false,
             requests_amount: 0,
@@ -116,6 +182,7 @@ mod tests {
         let pubkey_2 = Pubkey::new_unique();
         let user_2 = User {
             wallet: Default::default(),
+            pool: Default::default(),
             rate: 100,
             is_blocked: false,
             requests_amount: 0,
@@ -124,11 +191,13 @@ mod tests {
         let pubkey_3 = Pubkey::new_unique();
         let user_3 = User {
             wallet: Default::default(),
+            pool: Default::default(),
             rate: 200,
             is_blocked: false,
             requests_amount: 0,
             last_withdraw_index: 0,
         };
+        let lp_mint = Pubkey::new_unique();
         let collateral_address_1 = Pubkey::new_unique();
         let collateral_address_2 = Pubkey::new_unique();
         let collateral_address_3 = Pubkey::new_unique();
@@ -146,6 +215,10 @@ mod tests {
             creation_epoch: 0,
             bump: 0,
             is_native: false,
+            fee_paid_through_epoch: 0,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
         };
         let collateral_2 = Collateral {
             user: pubkey_1,
@@ -159,11 +232,15 @@ mod tests {
             creation_epoch: 0,
             bump: 0,
             is_native: false,
+            fee_paid_through_epoch: 0,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
         };
         let collateral_3 = Collateral {
             user: pubkey_2,
             pool: Default::default(),
-            stake_source: Default::default(),
+            stake_source: lp_mint,
             delegated_stake: Default::default(),
             delegation_stake: 100,
             amount: 0,
@@ -172,6 +249,10 @@ mod tests {
             creation_epoch: 0,
             bump: 0,
             is_native: false,
+            fee_paid_through_epoch: 0,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
         };
         let collateral_4 = Collateral {
             user: pubkey_3,
@@ -185,6 +266,10 @@ mod tests {
             creation_epoch: 0,
             bump: 0,
             is_native: false,
+            fee_paid_through_epoch: 0,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
         };
         let collateral_5 = Collateral {
             user: pubkey_3,
@@ -198,6 +283,10 @@ mod tests {
             creation_epoch: 0,
             bump: 0,
             is_native: false,
+            fee_paid_through_epoch: 0,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
         };
         let user_data = vec![(pubkey_1, user_1), (pubkey_2, user_2), (pubkey_3, user_3)];
         let collateral_data = vec![
@@ -207,12 +296,55 @@ mod tests {
             (collateral_address_4, collateral_4),
             (collateral_address_5, collateral_5),
         ];
-        let mut result = HashMap::new();
-        result.insert(collateral_address_2, 100);
-        result.insert(collateral_address_3, 50);
-        result.insert(collateral_address_4, 100);
-        result.insert(collateral_address_5, 1);
+        let result = vec![
+            QueueMember {
+                collateral: collateral_address_2,
+                user: pubkey_1,
+                amount: 100,
+                rate: 0,
+            },
+            QueueMember {
+                collateral: collateral_address_3,
+                user: pubkey_2,
+                amount: 75,
+                rate: 100,
+            },
+            QueueMember {
+                collateral: collateral_address_4,
+                user: pubkey_3,
+                amount: 100,
+                rate: 200,
+            },
+            QueueMember {
+                collateral: collateral_address_5,
+                user: pubkey_3,
+                amount: 1,
+                rate: 200,
+            },
+        ];
+
+        let stake_pools = HashMap::from([
+            (
+                Pubkey::default(),
+                StakePool {
+                    total_lamports: 1_000,
+                    pool_token_supply: 1_000,
+                    ..StakePool::default()
+                },
+            ),
+            (
+                lp_mint,
+                StakePool {
+                    total_lamports: 1_500,
+                    pool_token_supply: 1_000,
+                    ..StakePool::default()
+                },
+            ),
+        ]);
 
-        assert_eq!(generate_priority_queue(user_data, collateral_data, vec![]), result);
+        assert_eq!(
+            generate_priority_queue(user_data, collateral_data, vec![], &stake_pools),
+            result
+        );
     }
 }
@@ -55,10 +55,10 @@ pool.command('update')
   .description('Update pool')
   .requiredOption('-p, --pool <POOL>', 'Pool address to update')
   .option('-r, --fee-receiver <FEE_RECEIVER>', 'WFee receiver wallet to update')
-  .option('-w, --withdraw-fee <WITHDRAW_FEE>', 'Withdraw fee to update')
-  .option('-d, --deposit-fee <DEPOSIT_FEE>', 'Deposit fee to update')
-  .option('-m, --mint-fee <MINT_FEE>', 'Mint fee to update')
-  .option('-s, --storage-fee <STORAGE_FEE>', 'Storage fee to update')
+  .option('-w, --withdraw-fee <WITHDRAW_FEE>', 'Withdraw fee to update (in basis points)')
+  .option('-d, --deposit-fee <DEPOSIT_FEE>', 'Deposit fee to update (in basis points)')
+  .option('-m, --mint-fee <MINT_FEE>', 'Mint fee to update (in basis points)')
+  .option('-s, --storage-fee <STORAGE_FEE>', 'Storage fee to update (in basis points)')
   .option('-md, --min-deposit <MIN_DEPOSIT>', 'Minimal deposit value to update')
   .action(actions.updatePool)
 
@@ -296,7 +296,7 @@ const liquidationFee = cli.command('liquidationFee')
 liquidationFee.command('set')
   .description('Set liquidation fee')
   .option('-r, --fee-receiver <FEE_RECEIVER>', 'Wallet that will receive fee')
-  .option('-f, --fee <FEE>', 'Fee amount')
+  .option('-f, --fee <FEE>', 'Fee amount (in basis points)')
   .action(actions.setLiquidationFee)
 
 liquidationFee.command('show')
@@ -1,4 +1,7 @@
-use crate::*;
+use crate::{
+    state::{FeeMode, FeeType, LiquidationRoute, UpdatePoolData, WithdrawMode},
+    *,
+};
 
 #[event]
 pub struct DepositStakeEvent {
@@ -8,6 +11,7 @@ pub struct DepositStakeEvent {
     pub collateral: Pubkey,
     pub delegation_stake: u64,
     pub amount: u64,
+    pub lamports: u64,
     pub timestamp: i64,
 }
 
@@ -19,6 +23,8 @@ pub struct WithdrawStakeEvent {
     pub collateral: Pubkey,
     pub amount: u64,
     pub rest_amount: u64,
+    pub fee: u64,
+    pub fee_mode: FeeMode,
     pub timestamp: i64,
 }
 
@@ -30,6 +36,8 @@ pub struct LiquidationEvent {
     pub collateral: Pubkey,
     pub amount: u64,
     pub rest_amount: u64,
+    pub route: LiquidationRoute,
+    pub lamports_out: u64,
     pub timestamp: i64,
 }
 
@@ -39,6 +47,21 @@ pub struct WithdrawRequestCreationEvent {
     pub pool: Pubkey,
     pub user: Pubkey,
     pub amount: u64,
+    pub fee: u64,
+    pub fee_mode: FeeMode,
+    pub mode: WithdrawMode,
+    pub min_lamports_out: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CancelWithdrawRequestEvent {
+    #[index]
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub withdraw_info: Pubkey,
+    pub amount: u64,
+    pub fee_refund: u64,
     pub timestamp: i64,
 }
 
@@ -59,5 +82,133 @@ pub struct MintOmnisolEvent {
     #[index]
     pub collateral: Pubkey,
     pub amount: u64,
+    pub fee: u64,
+    pub fee_mode: FeeMode,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SyncCollateralEvent {
+    #[index]
+    pub pool: Pubkey,
+    #[index]
+    pub collateral: Pubkey,
+    pub rewards: u64,
+    pub delegation_stake: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeAccrualEvent {
+    #[index]
+    pub pool: Pubkey,
+    pub fee_type: FeeType,
+    pub fee_mode: FeeMode,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ReferralFeeEvent {
+    #[index]
+    pub pool: Pubkey,
+    #[index]
+    pub referrer: Pubkey,
+    pub amount: u64,
+    pub total_fees: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SweepTreasuryEvent {
+    #[index]
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SettleStorageFeeEvent {
+    #[index]
+    pub pool: Pubkey,
+    #[index]
+    pub collateral: Pubkey,
+    pub fee: u64,
+    pub accrued_storage_fee: u64,
+    pub fee_paid_through_epoch: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DeactivateCollateralEvent {
+    #[index]
+    pub pool: Pubkey,
+    #[index]
+    pub collateral: Pubkey,
+    pub vote_account: Pubkey,
+    pub epoch: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RedelegateCollateralEvent {
+    #[index]
+    pub pool: Pubkey,
+    #[index]
+    pub collateral: Pubkey,
+    pub previous_vote_account: Pubkey,
+    pub vote_account: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DelayedWithdrawalEvent {
+    #[index]
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub collateral: Pubkey,
+    pub withdraw_info: Pubkey,
+    pub stake_account: Pubkey,
+    pub amount: u64,
+    pub claimable_epoch: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ClaimWithdrawalEvent {
+    #[index]
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub withdraw_info: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolUpdateProposedEvent {
+    #[index]
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub data: UpdatePoolData,
+    pub activation_epoch: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolUpdateAppliedEvent {
+    #[index]
+    pub pool: Pubkey,
+    pub data: UpdatePoolData,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PoolUpdateCancelledEvent {
+    #[index]
+    pub pool: Pubkey,
+    pub authority: Pubkey,
     pub timestamp: i64,
 }
@@ -0,0 +1,26 @@
+use anchor_lang::prelude::*;
+
+use crate::state::GlobalConfig;
+
+/// The proposed admin can accept the admin role.
+pub fn handle(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let global_config = &mut ctx.accounts.global_config;
+
+    global_config.admin = ctx.accounts.authority.key();
+    global_config.pending_admin = None;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.pending_admin == Some(authority.key())
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+}
@@ -1,8 +1,8 @@
 use anchor_lang::prelude::*;
 
-use crate::state::{Liquidator, Manager};
+use crate::state::{GlobalConfig, Liquidator};
 
-/// The manager can add new liquidator.
+/// The admin can add new liquidator.
 /// Liquidator is the wallet that can liquidate collaterals.
 pub fn handle(ctx: Context<AddLiquidator>) -> Result<()> {
     let liquidator = &mut ctx.accounts.liquidator;
@@ -30,8 +30,12 @@ pub struct AddLiquidator<'info> {
     )]
     pub liquidator: Box<Account<'info, Liquidator>>,
 
-    #[account(mut, seeds = [Manager::SEED, authority.key().as_ref()], bump)]
-    pub manager: Box<Account<'info, Manager>>,
+    #[account(
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
 
     pub system_program: Program<'info, System>,
 }
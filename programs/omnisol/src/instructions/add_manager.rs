@@ -1,24 +1,39 @@
-use std::str::FromStr;
-
 use anchor_lang::prelude::*;
 
-use crate::state::{Manager, ADMIN};
+use crate::{
+    state::{GlobalConfig, Manager},
+    ErrorCode,
+};
 
 /// The admin can add new manager.
-pub fn handle(ctx: Context<AddManager>) -> Result<()> {
+/// Caller provides [permissions] bitmask of actions that the manager is allowed to do.
+pub fn handle(ctx: Context<AddManager>, permissions: u8) -> Result<()> {
     let manager = &mut ctx.accounts.manager;
     let manager_wallet = ctx.accounts.manager_wallet.key();
 
+    if !Manager::is_valid_permissions(permissions) {
+        msg!("Invalid permissions");
+        return Err(ErrorCode::WrongData.into());
+    }
+
     manager.manager = manager_wallet;
+    manager.permissions = permissions;
 
     Ok(())
 }
 
 #[derive(Accounts)]
 pub struct AddManager<'info> {
-    #[account(mut, constraint = authority.key() == Pubkey::from_str(ADMIN).unwrap())]
+    #[account(mut)]
     pub authority: Signer<'info>,
 
+    #[account(
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
     /// CHECK: Address of manager to add
     pub manager_wallet: AccountInfo<'info>,
 
@@ -43,7 +43,12 @@ pub struct AddToTokenWhitelist<'info> {
     )]
     pub whitelist: Box<Account<'info, Whitelist>>,
 
-    #[account(mut, seeds = [Manager::SEED, authority.key().as_ref()], bump)]
+    #[account(
+        mut,
+        seeds = [Manager::SEED, authority.key().as_ref()],
+        bump,
+        constraint = manager.has_permission(Manager::WHITELIST)
+    )]
     pub manager: Box<Account<'info, Manager>>,
 
     pub system_program: Program<'info, System>,
@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    events::PoolUpdateAppliedEvent,
+    state::{PendingPoolUpdate, Pool},
+    ErrorCode,
+};
+
+/// Anyone can apply the pool update once its timelock has expired.
+/// The pending update account is closed and its rent is returned to the proposer.
+pub fn handle(ctx: Context<ApplyPoolUpdate>) -> Result<()> {
+    let pending_pool_update = &ctx.accounts.pending_pool_update;
+    let clock = &ctx.accounts.clock;
+
+    if clock.epoch < pending_pool_update.activation_epoch {
+        msg!(
+            "Pool update can be applied since epoch {}",
+            pending_pool_update.activation_epoch
+        );
+        return Err(ErrorCode::PoolUpdateIsLocked.into());
+    }
+
+    let pool = &mut ctx.accounts.pool;
+    pending_pool_update.data.apply(pool);
+
+    emit!(PoolUpdateAppliedEvent {
+        pool: pool.key(),
+        data: pending_pool_update.data.clone(),
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ApplyPoolUpdate<'info> {
+    #[account(mut)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(
+        mut,
+        seeds = [PendingPoolUpdate::SEED, pool.key().as_ref()],
+        bump,
+        has_one = authority,
+        close = authority,
+    )]
+    pub pending_pool_update: Box<Account<'info, PendingPoolUpdate>>,
+
+    /// CHECK: Manager that proposed the update, receives rent of the pending update
+    #[account(mut)]
+    pub authority: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
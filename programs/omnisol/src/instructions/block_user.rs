@@ -1,12 +1,12 @@
 use anchor_lang::prelude::*;
 
 use crate::{
-    state::{Manager, User},
+    state::{Manager, Pool, User},
     ErrorCode,
 };
 
-/// The manager can block user.
-/// If the user is blocked, then all instructions on the platform are blocked for him
+/// The manager can block user in the pool.
+/// If the user is blocked, then all instructions of the pool are blocked for him
 pub fn handle(ctx: Context<BlockUser>) -> Result<()> {
     let user = &mut ctx.accounts.user;
 
@@ -27,13 +27,16 @@ pub struct BlockUser<'info> {
     #[account(
         mut,
         seeds = [Manager::SEED, authority.key().as_ref()],
-        bump
+        bump,
+        constraint = manager.has_permission(Manager::BLOCK)
     )]
     pub manager: Box<Account<'info, Manager>>,
 
+    pub pool: Box<Account<'info, Pool>>,
+
     #[account(
         mut,
-        seeds = [User::SEED, user_wallet.key().as_ref()],
+        seeds = [User::SEED, pool.key().as_ref(), user_wallet.key().as_ref()],
         bump
     )]
     pub user: Box<Account<'info, User>>,
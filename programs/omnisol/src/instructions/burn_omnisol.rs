@@ -3,7 +3,8 @@ use anchor_spl::token::{self, Token, TokenAccount};
 
 use crate::{
     events::WithdrawRequestCreationEvent,
-    state::{LiquidationFee, Pool, User, WithdrawInfo},
+    state::{FeeMode, LiquidationFee, Pool, Treasury, User, WithdrawInfo, WithdrawMode},
+    utils::fee::calc_fee,
     ErrorCode,
 };
 
@@ -11,7 +12,19 @@ use crate::{
 /// Burn a given amount of omniSOL and create a withdraw request.
 /// This request will be processed by the liquidator in some time.
 /// Caller provides some [amount] of omni-lamports that are to be burned.
-pub fn handle(ctx: Context<BurnOmnisol>, amount: u64) -> Result<()> {
+/// With in-kind fees the liquidation fee is withheld in omniSOL and burned, so less stake is unstaked for the request.
+/// The liquidation fee is escrowed until the request is fulfilled: the SOL fee in the pool treasury,
+/// the in-kind fee is minted to the liquidation fee receiver only for the fulfilled part of the request.
+/// Caller provides withdraw [mode]: instant requests are unstaked by the liquidator,
+/// delayed ones are deactivated and claimed with `claim_withdrawal` after the cooldown, without unstake fees.
+/// Caller provides [min_lamports_out] the user has to receive for the whole request, partial liquidations
+/// are checked against the proportional part of it.
+pub fn handle<'info>(
+    ctx: Context<'_, '_, '_, 'info, BurnOmnisol<'info>>,
+    amount: u64,
+    mode: WithdrawMode,
+    min_lamports_out: u64,
+) -> Result<()> {
     if amount == 0 {
         return Err(ErrorCode::InsufficientAmount.into());
     }
@@ -21,6 +34,11 @@ pub fn handle(ctx: Context<BurnOmnisol>, amount: u64) -> Result<()> {
         return Err(ErrorCode::PoolAlreadyPaused.into());
     }
 
+    if amount < pool.min_burn {
+        msg!("Minimal burn amount is {} lamports", pool.min_burn);
+        return Err(ErrorCode::AmountBelowMinimum.into());
+    }
+
     let pool_key = pool.key();
     let clock = &ctx.accounts.clock;
 
@@ -28,6 +46,7 @@ pub fn handle(ctx: Context<BurnOmnisol>, amount: u64) -> Result<()> {
 
     if user.wallet != ctx.accounts.authority.key() {
         user.wallet = ctx.accounts.authority.key();
+        user.pool = pool_key;
         user.rate = 0;
         user.is_blocked = false;
     }
@@ -36,23 +55,43 @@ pub fn handle(ctx: Context<BurnOmnisol>, amount: u64) -> Result<()> {
         return Err(ErrorCode::UserBlocked.into());
     }
 
-    let liquidation_fee = &mut ctx.accounts.liquidation_fee;
-
-    if liquidation_fee.fee > 0 {
-        let fee = amount.saturating_div(1000).saturating_mul(liquidation_fee.fee as u64);
-        msg!("Transfer liquidation fee: {} lamports", fee);
-
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.fee_payer.to_account_info(),
-                    to: ctx.accounts.fee_receiver.to_account_info(),
-                },
-            ),
-            fee,
-        )
-        .map_err(|_| ErrorCode::InsufficientFunds)?;
+    let fee = calc_fee(amount, ctx.accounts.liquidation_fee.fee);
+
+    if fee > 0 {
+        match pool.fee_mode {
+            FeeMode::Sol => {
+                msg!("Transfer liquidation fee: {} lamports", fee);
+
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.fee_payer.to_account_info(),
+                            to: ctx.accounts.treasury.to_account_info(),
+                        },
+                    ),
+                    fee,
+                )
+                .map_err(|_| ErrorCode::InsufficientFunds)?;
+
+                let treasury = &mut ctx.accounts.treasury;
+                treasury.escrowed_liquidation_fees = treasury.escrowed_liquidation_fees.saturating_add(fee);
+            }
+            FeeMode::InKind => {
+                msg!("Withhold liquidation fee: {} omni-lamports", fee);
+            }
+        }
+    }
+
+    // amount of omniSOL that will be burned and liquidated
+    let request_amount = match pool.fee_mode {
+        FeeMode::Sol => amount,
+        FeeMode::InKind => amount - fee,
+    };
+
+    if min_lamports_out > request_amount {
+        msg!("Minimal lamports out can't be greater than {}", request_amount);
+        return Err(ErrorCode::WrongData.into());
     }
 
     user.last_withdraw_index += 1;
@@ -73,13 +112,25 @@ pub fn handle(ctx: Context<BurnOmnisol>, amount: u64) -> Result<()> {
     let withdraw_info = &mut ctx.accounts.withdraw_info;
 
     withdraw_info.authority = ctx.accounts.authority.key();
-    withdraw_info.amount = amount;
+    withdraw_info.pool = pool_key;
+    withdraw_info.amount = request_amount;
     withdraw_info.created_at = clock.unix_timestamp;
+    withdraw_info.mode = mode;
+    withdraw_info.stake_account = Pubkey::default();
+    withdraw_info.claimable_epoch = 0;
+    withdraw_info.collateral_owner_wallet = Pubkey::default();
+    withdraw_info.min_lamports_out = min_lamports_out;
+    withdraw_info.fee = fee;
+    withdraw_info.fee_mode = pool.fee_mode;
 
     emit!(WithdrawRequestCreationEvent {
         pool: pool_key,
         user: user.key(),
-        amount,
+        amount: request_amount,
+        fee,
+        fee_mode: pool.fee_mode,
+        mode,
+        min_lamports_out,
         timestamp: clock.unix_timestamp,
     });
 
@@ -107,7 +158,7 @@ pub struct BurnOmnisol<'info> {
 
     #[account(
         init_if_needed,
-        seeds = [User::SEED, authority.key().as_ref()],
+        seeds = [User::SEED, pool.key().as_ref(), authority.key().as_ref()],
         bump,
         payer = authority,
         space = User::SIZE,
@@ -118,7 +169,7 @@ pub struct BurnOmnisol<'info> {
         init,
         seeds = [
             WithdrawInfo::SEED,
-            authority.key().as_ref(),
+            user.key().as_ref(),
             user.next_index().to_le_bytes().as_ref()
         ],
         bump,
@@ -134,13 +185,12 @@ pub struct BurnOmnisol<'info> {
     )]
     pub liquidation_fee: Box<Account<'info, LiquidationFee>>,
 
+    #[account(mut, seeds = [Treasury::SEED, pool.key().as_ref()], bump = treasury.bump)]
+    pub treasury: Box<Account<'info, Treasury>>,
+
     #[account(mut)]
     pub fee_payer: Signer<'info>,
 
-    /// CHECK: no needs to check, only for transfer
-    #[account(mut, address = liquidation_fee.fee_receiver)]
-    pub fee_receiver: AccountInfo<'info>,
-
     pub clock: Sysvar<'info, Clock>,
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
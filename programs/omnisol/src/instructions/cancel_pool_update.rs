@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    events::PoolUpdateCancelledEvent,
+    state::{Manager, PendingPoolUpdate, Pool},
+};
+
+/// The manager can cancel the pending pool update.
+/// The pending update account is closed and its rent is returned to the proposer.
+pub fn handle(ctx: Context<CancelPoolUpdate>) -> Result<()> {
+    emit!(PoolUpdateCancelledEvent {
+        pool: ctx.accounts.pool.key(),
+        authority: ctx.accounts.authority.key(),
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CancelPoolUpdate<'info> {
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(
+        mut,
+        seeds = [PendingPoolUpdate::SEED, pool.key().as_ref()],
+        bump,
+        close = proposer,
+    )]
+    pub pending_pool_update: Box<Account<'info, PendingPoolUpdate>>,
+
+    #[account(
+        mut,
+        seeds = [Manager::SEED, authority.key().as_ref()],
+        bump,
+        constraint = manager.has_permission(Manager::FEES)
+    )]
+    pub manager: Box<Account<'info, Manager>>,
+
+    pub authority: Signer<'info>,
+
+    /// CHECK: Manager that proposed the update, receives rent of the pending update
+    #[account(mut, address = pending_pool_update.authority)]
+    pub proposer: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
@@ -0,0 +1,176 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token::{self, Token, TokenAccount};
+
+use crate::{
+    events::CancelWithdrawRequestEvent,
+    state::{FeeMode, Pool, Treasury, User, WithdrawInfo, MINT_AUTHORITY_SEED},
+    utils,
+    utils::transfer_lamports,
+    ErrorCode,
+};
+
+/// The user can cancel the withdraw request, that isn't fulfilled yet.
+/// The rest amount of the request is minted back as omniSOL and the escrowed liquidation fee of it is refunded
+/// in the asset it was paid in: lamports from the pool treasury or omniSOL.
+/// Caller provides [index] of the request. Later requests of the user are passed as remaining accounts
+/// in the order of their indices, they are shifted down, so the requests keep their order.
+pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, CancelWithdrawRequest<'info>>, index: u32) -> Result<()> {
+    let pool = &ctx.accounts.pool;
+
+    if !pool.is_active {
+        return Err(ErrorCode::PoolAlreadyPaused.into());
+    }
+
+    let user = &mut ctx.accounts.user;
+
+    if user.is_blocked {
+        return Err(ErrorCode::UserBlocked.into());
+    }
+
+    let withdraw_info = &mut ctx.accounts.withdraw_info;
+
+    if withdraw_info.stake_account != Pubkey::default() {
+        msg!("Stake of the request is already deactivated");
+        return Err(ErrorCode::InvalidWithdrawMode.into());
+    }
+
+    let later_requests_amount = (user.last_withdraw_index - index) as usize;
+    if ctx.remaining_accounts.len() != later_requests_amount {
+        msg!("Expected {} later withdraw requests", later_requests_amount);
+        return Err(ErrorCode::WrongData.into());
+    }
+
+    let amount = withdraw_info.amount;
+    let fee_refund = withdraw_info.fee;
+
+    let mint_amount = match withdraw_info.fee_mode {
+        FeeMode::Sol => {
+            if fee_refund > 0 {
+                transfer_lamports(
+                    &ctx.accounts.treasury.to_account_info(),
+                    &ctx.accounts.authority.to_account_info(),
+                    fee_refund,
+                )?;
+
+                let treasury = &mut ctx.accounts.treasury;
+                treasury.escrowed_liquidation_fees = treasury
+                    .escrowed_liquidation_fees
+                    .checked_sub(fee_refund)
+                    .ok_or(ErrorCode::InsufficientFunds)?;
+            }
+
+            amount
+        }
+        FeeMode::InKind => amount.checked_add(fee_refund).ok_or(ErrorCode::TypeOverflow)?,
+    };
+
+    let mint_authority_seeds = [MINT_AUTHORITY_SEED, &[ctx.bumps["mint_authority"]]];
+
+    token::mint_to(
+        CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::MintTo {
+                mint: ctx.accounts.pool_mint.to_account_info(),
+                to: ctx.accounts.user_pool_token.to_account_info(),
+                authority: ctx.accounts.mint_authority.to_account_info(),
+            },
+            &[&mint_authority_seeds],
+        ),
+        mint_amount,
+    )?;
+
+    let withdraw_info_key = withdraw_info.key();
+    let user_key = user.key();
+    let mut last_withdraw_info: Option<&AccountInfo<'info>> = None;
+
+    // shift the later requests down to the place of the canceled one
+    for (next_index, next_withdraw_info_info) in (index + 1..=user.last_withdraw_index).zip(ctx.remaining_accounts) {
+        let (next_withdraw_info_key, _) = Pubkey::find_program_address(
+            &[WithdrawInfo::SEED, user_key.as_ref(), next_index.to_le_bytes().as_ref()],
+            ctx.program_id,
+        );
+        if next_withdraw_info_info.key() != next_withdraw_info_key {
+            msg!("Withdraw request {} is expected", next_index);
+            return Err(ErrorCode::WrongData.into());
+        }
+
+        let next_withdraw_info = Account::<WithdrawInfo>::try_from(next_withdraw_info_info)?.into_inner();
+
+        match last_withdraw_info {
+            None => withdraw_info.set_inner(next_withdraw_info),
+            Some(last_withdraw_info) => {
+                let mut data = last_withdraw_info.try_borrow_mut_data()?;
+                next_withdraw_info.try_serialize(&mut &mut data[..])?;
+            }
+        }
+
+        last_withdraw_info = Some(next_withdraw_info_info);
+    }
+
+    // close the withdraw_info account, that is left after the shift
+    let closed_withdraw_info = match last_withdraw_info {
+        Some(last_withdraw_info) => last_withdraw_info.to_account_info(),
+        None => withdraw_info.to_account_info(),
+    };
+    utils::close(closed_withdraw_info, ctx.accounts.authority.to_account_info())?;
+
+    user.last_withdraw_index -= 1;
+    user.requests_amount -= 1;
+
+    emit!(CancelWithdrawRequestEvent {
+        pool: pool.key(),
+        user: user_key,
+        withdraw_info: withdraw_info_key,
+        amount,
+        fee_refund,
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+#[instruction(index: u32)]
+pub struct CancelWithdrawRequest<'info> {
+    #[account(address = withdraw_info.pool)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: token program will check it
+    #[account(mut, address = pool.pool_mint)]
+    pub pool_mint: AccountInfo<'info>,
+
+    /// CHECK: no needs to check, only for signing
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
+    pub mint_authority: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        associated_token::mint = pool_mint,
+        associated_token::authority = authority,
+    )]
+    pub user_pool_token: Account<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [User::SEED, pool.key().as_ref(), authority.key().as_ref()],
+        bump,
+    )]
+    pub user: Box<Account<'info, User>>,
+
+    #[account(
+        mut,
+        seeds = [WithdrawInfo::SEED, user.key().as_ref(), index.to_le_bytes().as_ref()],
+        bump,
+        constraint = withdraw_info.authority == authority.key(),
+    )]
+    pub withdraw_info: Box<Account<'info, WithdrawInfo>>,
+
+    #[account(mut, seeds = [Treasury::SEED, pool.key().as_ref()], bump = treasury.bump)]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, Token>,
+}
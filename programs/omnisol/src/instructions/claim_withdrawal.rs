@@ -0,0 +1,145 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    events::ClaimWithdrawalEvent,
+    state::{Pool, User, WithdrawInfo, WithdrawMode},
+    utils,
+    utils::stake,
+    ErrorCode,
+};
+
+/// Anyone can claim the delayed withdraw request after its stake is deactivated.
+/// The request amount is withdrawn straight to the user, that made this request,
+/// the rest lamports of the stake account (rent, excess and rewards) go back to the collateral owner.
+pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, ClaimWithdrawal<'info>>) -> Result<()> {
+    let pool = &ctx.accounts.pool;
+    let withdraw_info = &ctx.accounts.withdraw_info;
+    let clock = &ctx.accounts.clock;
+
+    if withdraw_info.mode != WithdrawMode::Delayed {
+        return Err(ErrorCode::InvalidWithdrawMode.into());
+    }
+
+    let stake_account = &ctx.accounts.stake_account;
+    let is_inactive = stake_account
+        .delegation()
+        .map(|delegation| stake::is_inactive(&delegation, clock.epoch, &ctx.accounts.stake_history))
+        .unwrap_or(true);
+
+    if clock.epoch < withdraw_info.claimable_epoch || !is_inactive {
+        msg!("Withdrawal is claimable since epoch {}", withdraw_info.claimable_epoch);
+        return Err(ErrorCode::WithdrawalNotClaimable.into());
+    }
+
+    let pool_key = pool.key();
+    let pool_authority_seeds = [pool_key.as_ref(), &[pool.authority_bump]];
+    let lamports = stake_account.to_account_info().lamports();
+    let amount = withdraw_info.amount.min(lamports);
+
+    if amount < withdraw_info.min_lamports_out {
+        msg!(
+            "Stake account has {} lamports, expected at least {}",
+            lamports,
+            withdraw_info.min_lamports_out
+        );
+        return Err(ErrorCode::SlippageExceeded.into());
+    }
+
+    let withdraw = |to: AccountInfo<'info>, lamports: u64| {
+        stake::withdraw(
+            CpiContext::new_with_signer(
+                ctx.accounts.stake_program.to_account_info(),
+                stake::Withdraw {
+                    stake: stake_account.to_account_info(),
+                    to,
+                    authority: ctx.accounts.pool_authority.to_account_info(),
+                    clock: clock.to_account_info(),
+                    stake_history: ctx.accounts.stake_history.to_account_info(),
+                },
+                &[&pool_authority_seeds],
+            ),
+            lamports,
+        )
+    };
+
+    let user_wallet = ctx.accounts.user_wallet.to_account_info();
+    let collateral_owner_wallet = ctx.accounts.collateral_owner_wallet.to_account_info();
+    let rest_lamports = lamports - amount;
+    let rent_exempt_reserve = stake_account
+        .meta()
+        .map(|meta| meta.rent_exempt_reserve)
+        .unwrap_or_default();
+
+    // a partial withdrawal has to leave the rent-exempt reserve, the last one empties the stake account
+    if rest_lamports == 0 {
+        withdraw(user_wallet, amount)?;
+    } else if rest_lamports >= rent_exempt_reserve {
+        withdraw(user_wallet, amount)?;
+        withdraw(collateral_owner_wallet, rest_lamports)?;
+    } else {
+        withdraw(collateral_owner_wallet, rest_lamports)?;
+        withdraw(user_wallet, amount)?;
+    }
+
+    // close the withdraw_info account
+    utils::close(
+        withdraw_info.to_account_info(),
+        ctx.accounts.user_wallet.to_account_info(),
+    )?;
+
+    let user = &mut ctx.accounts.user;
+    user.requests_amount -= 1;
+
+    emit!(ClaimWithdrawalEvent {
+        pool: pool_key,
+        user: user.key(),
+        withdraw_info: withdraw_info.key(),
+        amount,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ClaimWithdrawal<'info> {
+    #[account(address = withdraw_info.pool)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: no needs to check, only for signing
+    #[account(seeds = [pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    /// CHECK:
+    #[account(mut, address = withdraw_info.authority)]
+    pub user_wallet: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [User::SEED, withdraw_info.pool.as_ref(), user_wallet.key().as_ref()],
+        bump,
+    )]
+    pub user: Box<Account<'info, User>>,
+
+    #[account(
+        mut,
+        seeds = [
+            WithdrawInfo::SEED,
+            user.key().as_ref(),
+            user.get_index().to_le_bytes().as_ref(),
+        ],
+        bump,
+    )]
+    pub withdraw_info: Box<Account<'info, WithdrawInfo>>,
+
+    /// CHECK:
+    #[account(mut, address = withdraw_info.collateral_owner_wallet)]
+    pub collateral_owner_wallet: AccountInfo<'info>,
+
+    #[account(mut, address = withdraw_info.stake_account)]
+    pub stake_account: Box<Account<'info, stake::StakeAccount>>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_history: Sysvar<'info, StakeHistory>,
+    pub stake_program: Program<'info, stake::Stake>,
+}
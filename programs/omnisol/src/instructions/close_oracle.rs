@@ -1,29 +1,34 @@
-use std::str::FromStr;
-
 use anchor_lang::prelude::*;
 
-use crate::state::{Oracle, ADMIN};
+use crate::{
+    state::{GlobalConfig, Oracle},
+    utils,
+};
 
-/// The admin can close oracle
-pub fn handle(_ctx: Context<CloseOracle>) -> Result<()> {
-    Ok(())
+/// The admin can close oracle.
+/// The account isn't deserialized, so an oracle of any layout can be closed and created again.
+pub fn handle(ctx: Context<CloseOracle>) -> Result<()> {
+    utils::close(
+        ctx.accounts.oracle.to_account_info(),
+        ctx.accounts.authority.to_account_info(),
+    )
 }
 
 #[derive(Accounts)]
 pub struct CloseOracle<'info> {
-    #[account(
-        mut,
-        address = Pubkey::from_str(ADMIN).unwrap()
-    )]
+    #[account(mut)]
     pub authority: Signer<'info>,
 
     #[account(
-        mut,
-        seeds = [Oracle::SEED],
+        seeds = [GlobalConfig::SEED],
         bump,
-        close = authority
+        constraint = global_config.admin == authority.key()
     )]
-    pub oracle: Box<Account<'info, Oracle>>,
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    /// CHECK: Oracle account of the current or a previous layout
+    #[account(mut, seeds = [Oracle::SEED], bump, owner = crate::ID)]
+    pub oracle: AccountInfo<'info>,
 
     pub system_program: Program<'info, System>,
 }
@@ -1,14 +1,29 @@
 use anchor_lang::prelude::*;
 
-use crate::{state::Pool, ErrorCode};
+use crate::{
+    state::{Pool, Treasury},
+    ErrorCode,
+};
 
 /// The pool manager can close pool.
+/// The pool treasury is closed with it, so all fees have to be swept from it first.
 pub fn handle(ctx: Context<ClosePool>) -> Result<()> {
     if ctx.accounts.pool.collaterals_amount > 0 {
         msg!("Please, wait until all collaterals will be closed");
         return Err(ErrorCode::StillRemainingCollaterals.into());
     }
 
+    let treasury = ctx.accounts.treasury.to_account_info();
+    let rent_exempt_balance = ctx.accounts.rent.minimum_balance(treasury.data_len());
+
+    if treasury.lamports() > rent_exempt_balance {
+        msg!(
+            "Please, sweep {} lamports from the treasury first",
+            treasury.lamports() - rent_exempt_balance
+        );
+        return Err(ErrorCode::TreasuryNotEmpty.into());
+    }
+
     Ok(())
 }
 
@@ -17,8 +32,18 @@ pub struct ClosePool<'info> {
     #[account(mut, close = authority, has_one = authority)]
     pub pool: Box<Account<'info, Pool>>,
 
+    #[account(
+        mut,
+        seeds = [Treasury::SEED, pool.key().as_ref()],
+        bump = treasury.bump,
+        has_one = pool,
+        close = authority,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
     #[account(mut)]
     pub authority: Signer<'info>,
 
+    pub rent: Sysvar<'info, Rent>,
     pub system_program: Program<'info, System>,
 }
@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Oracle;
+
+/// The oracle can commit priority queue data.
+/// The staging queue filled by `update_oracle_info` becomes active in a single instruction,
+/// so liquidators never see a partially written queue.
+pub fn handle(ctx: Context<CommitOracleInfo>) -> Result<()> {
+    let oracle = &mut ctx.accounts.oracle;
+
+    oracle.commit();
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct CommitOracleInfo<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [Oracle::SEED],
+        bump,
+        has_one = authority
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
+}
@@ -0,0 +1,142 @@
+use anchor_lang::{prelude::*, solana_program::vote};
+
+use crate::{
+    events::*,
+    state::{Collateral, Manager, Oracle, Pool, User, Validator},
+    utils::{collateral::sync_rewards, stake, validator::load_validator},
+    ErrorCode,
+};
+
+/// The manager can deactivate the stake of the native collateral to move it to another validator,
+/// e.g. if its current validator becomes delinquent.
+/// The new validator has to be accepted by the pool's validator policy and have capacity for the collateral,
+/// that is reserved in its entry until the redelegation.
+/// Rewards of the collateral are synced before the deactivation, so they don't count as the redelegation excess.
+/// Once the stake is inactive, anyone can redelegate it to the new validator with `redelegate_collateral`.
+pub fn handle(ctx: Context<DeactivateCollateral>) -> Result<()> {
+    let collateral = &mut ctx.accounts.collateral;
+
+    if !collateral.is_native || collateral.pending_vote_account.is_some() {
+        return Err(ErrorCode::InvalidStakeAccount.into());
+    }
+
+    let delegation = ctx
+        .accounts
+        .delegated_stake
+        .delegation()
+        .ok_or(ErrorCode::InvalidStakeAccount)?;
+
+    if delegation.voter_pubkey == ctx.accounts.vote_account.key() {
+        msg!("Stake is already delegated to the validator");
+        return Err(ErrorCode::WrongData.into());
+    }
+
+    let clock = &ctx.accounts.clock;
+
+    sync_rewards(
+        &mut ctx.accounts.pool,
+        &mut ctx.accounts.user,
+        collateral,
+        &mut ctx.accounts.oracle,
+        &ctx.accounts.previous_validator,
+        delegation.stake,
+        clock,
+    )?;
+
+    let pool = &ctx.accounts.pool;
+    let validator = load_validator(&ctx.accounts.validator)?;
+
+    if !pool.validator_policy.allows(validator.as_deref()) {
+        return Err(ErrorCode::ValidatorNotAllowed.into());
+    }
+
+    if let Some(mut validator) = validator {
+        let amount = collateral.get_rest_amount();
+        if !validator.has_capacity(amount) {
+            msg!("Validator collateral cap is {} lamports", validator.max_collateral);
+            return Err(ErrorCode::ValidatorCapExceeded.into());
+        }
+
+        validator.collateral_amount = validator.collateral_amount.saturating_add(amount);
+        validator.exit(&crate::ID)?;
+    }
+
+    let pool_key = pool.key();
+    let pool_authority_seeds = [pool_key.as_ref(), &[pool.authority_bump]];
+
+    stake::deactivate(CpiContext::new_with_signer(
+        ctx.accounts.stake_program.to_account_info(),
+        stake::Deactivate {
+            stake: ctx.accounts.delegated_stake.to_account_info(),
+            authority: ctx.accounts.pool_authority.to_account_info(),
+            clock: clock.to_account_info(),
+        },
+        &[&pool_authority_seeds],
+    ))?;
+
+    collateral.pending_vote_account = Some(ctx.accounts.vote_account.key());
+
+    emit!(DeactivateCollateralEvent {
+        pool: pool_key,
+        collateral: collateral.key(),
+        vote_account: ctx.accounts.vote_account.key(),
+        epoch: clock.epoch,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct DeactivateCollateral<'info> {
+    #[account(mut, address = collateral.pool)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: no needs to check, only for signing
+    #[account(seeds = [pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    #[account(mut, address = collateral.user)]
+    pub user: Box<Account<'info, User>>,
+
+    #[account(mut)]
+    pub collateral: Box<Account<'info, Collateral>>,
+
+    #[account(mut, constraint = collateral.delegated_stake == delegated_stake.key())]
+    pub delegated_stake: Box<Account<'info, stake::StakeAccount>>,
+
+    /// CHECK: Vote account of the validator to redelegate the stake to
+    #[account(owner = vote::program::ID)]
+    pub vote_account: AccountInfo<'info>,
+
+    /// CHECK: validator entry of the current vote account, may be uninitialized
+    #[account(
+        mut,
+        seeds = [
+            Validator::SEED,
+            pool.key().as_ref(),
+            delegated_stake.delegation().map(|d| d.voter_pubkey).unwrap_or_default().as_ref(),
+        ],
+        bump,
+    )]
+    pub previous_validator: AccountInfo<'info>,
+
+    /// CHECK: validator entry of the vote account, may be uninitialized
+    #[account(mut, seeds = [Validator::SEED, pool.key().as_ref(), vote_account.key().as_ref()], bump)]
+    pub validator: AccountInfo<'info>,
+
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [Manager::SEED, authority.key().as_ref()],
+        bump,
+        constraint = manager.has_permission(Manager::STAKE)
+    )]
+    pub manager: Box<Account<'info, Manager>>,
+
+    #[account(mut, seeds = [Oracle::SEED], bump)]
+    pub oracle: Box<Account<'info, Oracle>>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_program: Program<'info, stake::Stake>,
+}
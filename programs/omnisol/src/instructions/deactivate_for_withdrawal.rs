@@ -0,0 +1,240 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    events::*,
+    state::{Collateral, FeeType, Liquidator, Oracle, Pool, Treasury, User, Validator, WithdrawInfo, WithdrawMode},
+    utils,
+    utils::{fee::record_fee, stake, validator::load_validator},
+    ErrorCode,
+};
+
+/// The liquidator can get native collateral from priority queue and deactivate its stake to process delayed withdraw request.
+/// The whole request is covered by one stake account, that the user claims after the cooldown with `claim_withdrawal`.
+/// If the whole collateral stake is deactivated, the collateral no longer references it,
+/// and its lamports above the request amount are returned to the collateral owner on the claim.
+/// The deactivated stake is released from the cap of its validator entry.
+pub fn handle(ctx: Context<DeactivateForWithdrawal>) -> Result<()> {
+    let pool = &mut ctx.accounts.pool;
+
+    if !pool.is_active {
+        return Err(ErrorCode::PoolAlreadyPaused.into());
+    }
+
+    if ctx.accounts.user.is_blocked {
+        return Err(ErrorCode::UserBlocked.into());
+    }
+
+    let withdraw_info = &mut ctx.accounts.withdraw_info;
+
+    if withdraw_info.mode != WithdrawMode::Delayed || withdraw_info.stake_account != Pubkey::default() {
+        return Err(ErrorCode::InvalidWithdrawMode.into());
+    }
+
+    let collateral = &mut ctx.accounts.collateral;
+
+    if !collateral.is_native {
+        return Err(ErrorCode::InvalidStakeAccount.into());
+    }
+
+    if collateral.pending_vote_account.is_some() {
+        msg!("Collateral stake is being redelegated");
+        return Err(ErrorCode::StakeNotActive.into());
+    }
+
+    let amount = withdraw_info.amount;
+    let rest_amount = collateral.get_rest_amount();
+
+    if amount == 0 || amount > rest_amount {
+        return Err(ErrorCode::InsufficientAmount.into());
+    }
+
+    let oracle = &mut ctx.accounts.oracle;
+
+    let mut queue_member = oracle
+        .priority_queue
+        .iter()
+        .find(|queue_member| queue_member.collateral == collateral.key() && queue_member.amount == rest_amount)
+        .cloned()
+        .ok_or::<Error>(ErrorCode::WrongData.into())?;
+
+    queue_member.amount -= amount;
+    oracle.upsert(queue_member);
+
+    let pool_key = pool.key();
+    let pool_authority_seeds = [pool_key.as_ref(), &[pool.authority_bump]];
+    let clock = &ctx.accounts.clock;
+
+    let stake_account = if amount < rest_amount {
+        // Split new stake from existing stake
+        stake::split(
+            CpiContext::new_with_signer(
+                ctx.accounts.stake_program.to_account_info(),
+                stake::Split {
+                    stake: ctx.accounts.delegated_stake.to_account_info(),
+                    split_stake: ctx.accounts.split_stake.to_account_info(),
+                    authority: ctx.accounts.pool_authority.to_account_info(),
+                    system_program: ctx.accounts.system_program.to_account_info(),
+                },
+                &[&pool_authority_seeds],
+            ),
+            amount,
+        )?;
+        ctx.accounts.split_stake.to_account_info()
+    } else {
+        ctx.accounts.delegated_stake.to_account_info()
+    };
+
+    stake::deactivate(CpiContext::new_with_signer(
+        ctx.accounts.stake_program.to_account_info(),
+        stake::Deactivate {
+            stake: stake_account.clone(),
+            authority: ctx.accounts.pool_authority.to_account_info(),
+            clock: clock.to_account_info(),
+        },
+        &[&pool_authority_seeds],
+    ))?;
+
+    // the owner pays the storage fee for the whole collateral up to the deactivation
+    collateral.settle_storage_fee(pool.storage_fee, clock.epoch);
+    collateral.liquidated_amount += amount;
+
+    if let Some(mut validator) = load_validator(&ctx.accounts.validator)? {
+        validator.collateral_amount = validator.collateral_amount.saturating_sub(amount);
+        validator.exit(&crate::ID)?;
+    }
+
+    withdraw_info.stake_account = stake_account.key();
+    withdraw_info.claimable_epoch = clock.epoch + 1;
+    withdraw_info.collateral_owner_wallet = ctx.accounts.collateral_owner_wallet.key();
+
+    if amount == rest_amount {
+        // the stake account now belongs to the request
+        collateral.delegated_stake = Pubkey::default();
+    }
+
+    // the request can't be canceled anymore, so the escrowed liquidation fee is earned
+    let fee = withdraw_info.fee;
+    withdraw_info.fee = 0;
+    ctx.accounts
+        .treasury
+        .release_liquidation_fee(withdraw_info.fee_mode, fee);
+    record_fee(
+        &mut ctx.accounts.treasury,
+        FeeType::Liquidation,
+        withdraw_info.fee_mode,
+        fee,
+        clock.unix_timestamp,
+    );
+
+    pool.deposit_amount = pool
+        .deposit_amount
+        .checked_sub(amount)
+        .ok_or(ErrorCode::InsufficientAmount)?;
+
+    if collateral.is_closable(collateral.delegation_stake) {
+        // close the collateral account
+        utils::close(
+            collateral.to_account_info(),
+            ctx.accounts.collateral_owner_wallet.to_account_info(),
+        )?;
+
+        pool.collaterals_amount = pool.collaterals_amount.saturating_sub(1);
+    }
+
+    emit!(DelayedWithdrawalEvent {
+        pool: pool_key,
+        authority: ctx.accounts.authority.key(),
+        collateral: collateral.key(),
+        withdraw_info: withdraw_info.key(),
+        stake_account: stake_account.key(),
+        amount,
+        claimable_epoch: withdraw_info.claimable_epoch,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct DeactivateForWithdrawal<'info> {
+    #[account(mut, address = collateral.pool)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: no needs to check, only for signing
+    #[account(seeds = [pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    #[account(mut, constraint = collateral.user == collateral_owner.key())]
+    pub collateral: Box<Account<'info, Collateral>>,
+
+    #[account(constraint = collateral_owner.wallet == collateral_owner_wallet.key())]
+    pub collateral_owner: Box<Account<'info, User>>,
+
+    /// CHECK:
+    #[account(mut)]
+    pub collateral_owner_wallet: AccountInfo<'info>,
+
+    /// CHECK:
+    #[account(address = withdraw_info.authority)]
+    pub user_wallet: AccountInfo<'info>,
+
+    #[account(
+        seeds = [User::SEED, withdraw_info.pool.as_ref(), user_wallet.key().as_ref()],
+        bump,
+    )]
+    pub user: Box<Account<'info, User>>,
+
+    #[account(
+        mut,
+        seeds = [
+            WithdrawInfo::SEED,
+            user.key().as_ref(),
+            user.get_index().to_le_bytes().as_ref(),
+        ],
+        bump,
+        constraint = withdraw_info.pool == pool.key(),
+    )]
+    pub withdraw_info: Box<Account<'info, WithdrawInfo>>,
+
+    #[account(mut, seeds = [Treasury::SEED, pool.key().as_ref()], bump = treasury.bump)]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(
+        mut,
+        seeds = [Oracle::SEED],
+        bump,
+    )]
+    pub oracle: Box<Account<'info, Oracle>>,
+
+    #[account(mut, address = collateral.delegated_stake)]
+    pub delegated_stake: Box<Account<'info, stake::StakeAccount>>,
+
+    /// CHECK: validator entry of the delegated stake's vote account, may be uninitialized
+    #[account(
+        mut,
+        seeds = [
+            Validator::SEED,
+            pool.key().as_ref(),
+            delegated_stake.delegation().map(|d| d.voter_pubkey).unwrap_or_default().as_ref(),
+        ],
+        bump,
+    )]
+    pub validator: AccountInfo<'info>,
+
+    /// New stake account for the withdrawal, unused if the whole collateral is deactivated
+    #[account(mut)]
+    pub split_stake: Signer<'info>,
+
+    #[account(
+        seeds = [Liquidator::SEED, authority.key().as_ref()],
+        bump,
+    )]
+    pub liquidator: Box<Account<'info, Liquidator>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_program: Program<'info, stake::Stake>,
+    pub system_program: Program<'info, System>,
+}
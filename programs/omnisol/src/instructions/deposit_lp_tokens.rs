@@ -3,13 +3,19 @@ use anchor_spl::token;
 
 use crate::{
     events::*,
-    state::{Collateral, Pool, User, Whitelist},
+    state::{Collateral, FeeMode, FeeType, Oracle, Pool, QueueMember, Treasury, User, Whitelist},
+    utils::{
+        fee::{calc_fee, pay_referral_fee, record_fee},
+        stake_pool::{get_lamports_value, get_stake_pool},
+    },
     ErrorCode,
 };
 
 /// The user can use their deposit as collateral.
 /// Caller provides some [amount] of lp-token-lamports that are to be delegated.
-pub fn handle(ctx: Context<DepositLPTokens>, amount: u64) -> Result<()> {
+/// The deposit is valued in lamports by the exchange rate of the whitelisted stake pool.
+/// The referral account and the referrer wallet can be passed as remaining accounts to share the deposit fee.
+pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, DepositLPTokens<'info>>, amount: u64) -> Result<()> {
     if amount == 0 {
         return Err(ErrorCode::InsufficientAmount.into());
     }
@@ -22,8 +28,27 @@ pub fn handle(ctx: Context<DepositLPTokens>, amount: u64) -> Result<()> {
     let pool_key = pool.key();
     let clock = &ctx.accounts.clock;
 
+    let stake_pool = get_stake_pool(&ctx.accounts.stake_pool, &ctx.accounts.whitelist)?;
+    let lamports = get_lamports_value(&stake_pool, amount)?;
+
+    if lamports < pool.min_deposit {
+        msg!("Minimal deposit is {} lamports", pool.min_deposit);
+        return Err(ErrorCode::DepositBelowMinimum.into());
+    }
+
     if pool.deposit_fee > 0 {
-        let fee = amount.saturating_div(1000).saturating_mul(pool.deposit_fee as u64);
+        let fee = calc_fee(lamports, pool.deposit_fee);
+        let referral_fee = pay_referral_fee(
+            ctx.remaining_accounts,
+            pool.key(),
+            pool.referral_share,
+            ctx.accounts.authority.key(),
+            ctx.accounts.fee_payer.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            fee,
+            ctx.accounts.clock.unix_timestamp,
+        )?;
+        let fee = fee - referral_fee;
         msg!("Transfer deposit fee: {} lamports", fee);
 
         system_program::transfer(
@@ -31,12 +56,20 @@ pub fn handle(ctx: Context<DepositLPTokens>, amount: u64) -> Result<()> {
                 ctx.accounts.system_program.to_account_info(),
                 system_program::Transfer {
                     from: ctx.accounts.fee_payer.to_account_info(),
-                    to: ctx.accounts.fee_receiver.to_account_info(),
+                    to: ctx.accounts.treasury.to_account_info(),
                 },
             ),
             fee,
         )
         .map_err(|_| ErrorCode::InsufficientFunds)?;
+
+        record_fee(
+            &mut ctx.accounts.treasury,
+            FeeType::Deposit,
+            FeeMode::Sol,
+            fee,
+            ctx.accounts.clock.unix_timestamp,
+        );
     }
 
     // Transfer LP tokens to the pool
@@ -56,6 +89,7 @@ pub fn handle(ctx: Context<DepositLPTokens>, amount: u64) -> Result<()> {
 
     if user.wallet != ctx.accounts.authority.key() {
         user.wallet = ctx.accounts.authority.key();
+        user.pool = pool_key;
         user.rate = 0;
         user.is_blocked = false;
 
@@ -81,20 +115,37 @@ pub fn handle(ctx: Context<DepositLPTokens>, amount: u64) -> Result<()> {
         collateral.creation_epoch = clock.epoch;
         collateral.bump = ctx.bumps["collateral"];
         collateral.is_native = false;
+        collateral.fee_paid_through_epoch = clock.epoch;
+        collateral.accrued_storage_fee = 0;
+        collateral.pending_vote_account = None;
+        collateral.redelegation_excess = 0;
         pool.collaterals_amount = pool.collaterals_amount.saturating_add(1);
     }
 
-    user.rate += amount;
+    // top-up is not charged for the epochs before the deposit
+    collateral.settle_storage_fee(pool.storage_fee, clock.epoch);
+
+    user.rate += lamports;
     collateral.delegation_stake += amount;
 
     pool.deposit_amount = pool.deposit_amount.checked_add(amount).ok_or(ErrorCode::TypeOverflow)?;
 
+    let oracle = &mut ctx.accounts.oracle;
+    oracle.set_rate(user.key(), user.rate);
+    oracle.upsert(QueueMember {
+        collateral: collateral.key(),
+        user: user.key(),
+        amount: get_lamports_value(&stake_pool, collateral.get_rest_amount())?,
+        rate: user.rate,
+    });
+
     emit!(DepositStakeEvent {
         pool: pool.key(),
         collateral: collateral.key(),
         delegation_stake: collateral.delegation_stake,
         timestamp: clock.unix_timestamp,
         amount,
+        lamports,
     });
 
     Ok(())
@@ -111,7 +162,7 @@ pub struct DepositLPTokens<'info> {
 
     #[account(
         init_if_needed,
-        seeds = [User::SEED, authority.key().as_ref()],
+        seeds = [User::SEED, pool.key().as_ref(), authority.key().as_ref()],
         bump,
         payer = authority,
         space = User::SIZE,
@@ -127,6 +178,9 @@ pub struct DepositLPTokens<'info> {
     )]
     pub collateral: Account<'info, Collateral>,
 
+    #[account(mut, seeds = [Oracle::SEED], bump)]
+    pub oracle: Box<Account<'info, Oracle>>,
+
     #[account(
         mut,
         associated_token::mint = lp_token,
@@ -151,15 +205,17 @@ pub struct DepositLPTokens<'info> {
     #[account(address = whitelist.mint)]
     pub lp_token: AccountInfo<'info>,
 
+    /// CHECK: SPL stake pool of the LP token, will be checked via whitelist
+    pub stake_pool: AccountInfo<'info>,
+
     #[account(mut)]
     pub authority: Signer<'info>,
 
     #[account(mut)]
     pub fee_payer: Signer<'info>,
 
-    /// CHECK: no needs to check, only for transfer
-    #[account(mut, address = pool.fee_receiver)]
-    pub fee_receiver: AccountInfo<'info>,
+    #[account(mut, seeds = [Treasury::SEED, pool.key().as_ref()], bump = treasury.bump)]
+    pub treasury: Box<Account<'info, Treasury>>,
 
     pub clock: Sysvar<'info, Clock>,
     pub token_program: Program<'info, token::Token>,
@@ -1,15 +1,40 @@
-use anchor_lang::{prelude::*, solana_program::stake::state::StakeAuthorize, system_program};
+use anchor_lang::{
+    prelude::*,
+    solana_program::stake::{state::StakeAuthorize, MINIMUM_STAKE_DELEGATION},
+    system_program,
+};
 
 use crate::{
     events::*,
-    state::{Collateral, Pool, User},
-    utils::stake,
+    state::{Collateral, FeeMode, FeeType, Oracle, Pool, QueueMember, Treasury, User, Validator},
+    utils::{
+        fee::{calc_fee, pay_referral_fee, record_fee},
+        stake,
+        validator::load_validator,
+    },
     ErrorCode,
 };
 
 /// The user can use their deposit as collateral.
 /// As their stake accounts continue to earn yield, the amount of lamports under them increases.
-pub fn handle(ctx: Context<DepositStake>, amount: u64) -> Result<()> {
+/// The referral account and the referrer wallet can be passed as remaining accounts to share the deposit fee.
+/// Only the delegated stake is counted as collateral, the rent-exempt reserve and excess lamports are not.
+/// A partial deposit splits [amount] plus the rent-exempt reserve of the new stake account from the source one,
+/// so the delegation of both accounts can't be less than the minimum delegation.
+/// The whole stake has to be active, activating or deactivating stake can't be deposited.
+/// The stake has to be delegated to a validator accepted by the pool's validator policy
+/// and can't exceed the validator's collateral cap.
+/// Caller provides [with_merge] flag that indicates the possibility to merge.
+/// If [with_merge] is true, than the deposited stake is merged into the stake account of the existing collateral,
+/// that has to be fully active and delegated to the same validator, instead of creating a new collateral.
+/// The merged rent-exempt reserve and undelegated lamports of the deposited stake are refunded to the user.
+/// The collateral address is derived from the collateral user account, that is the global user account
+/// of the user for collaterals migrated from it.
+pub fn handle<'info>(
+    ctx: Context<'_, '_, '_, 'info, DepositStake<'info>>,
+    amount: u64,
+    with_merge: bool,
+) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
 
     if !pool.is_active {
@@ -22,18 +47,104 @@ pub fn handle(ctx: Context<DepositStake>, amount: u64) -> Result<()> {
         .delegation()
         .ok_or(ErrorCode::InvalidStakeAccount)?;
 
+    if !stake::is_fully_active(&delegation, ctx.accounts.clock.epoch, &ctx.accounts.stake_history) {
+        return Err(ErrorCode::StakeNotActive.into());
+    }
+
     if amount == 0 || amount > delegation.stake {
         return Err(ErrorCode::InsufficientAmount.into());
     }
 
-    if (amount == delegation.stake && ctx.accounts.delegated_stake.key() == ctx.accounts.split_stake.key())
+    let rent_exempt_reserve = ctx
+        .accounts
+        .source_stake
+        .meta()
+        .ok_or(ErrorCode::InvalidStakeAccount)?
+        .rent_exempt_reserve;
+
+    // a partial deposit is split from the source stake
+    let split_lamports = if amount < delegation.stake {
+        let split_lamports = stake::get_split_lamports(amount, delegation.stake, rent_exempt_reserve);
+
+        if split_lamports.is_none() {
+            msg!(
+                "Split can't leave less than {} lamports delegated, rent-exempt reserve is {} lamports",
+                MINIMUM_STAKE_DELEGATION,
+                rent_exempt_reserve
+            );
+            return Err(ErrorCode::InsufficientAmount.into());
+        }
+
+        split_lamports
+    } else {
+        None
+    };
+
+    if amount < pool.min_deposit {
+        msg!("Minimal deposit is {} lamports", pool.min_deposit);
+        return Err(ErrorCode::DepositBelowMinimum.into());
+    }
+
+    let mut validator = load_validator(&ctx.accounts.validator)?;
+
+    if !pool.validator_policy.allows(validator.as_deref()) {
+        msg!("Validator {} is not allowed", delegation.voter_pubkey);
+        return Err(ErrorCode::ValidatorNotAllowed.into());
+    }
+
+    if let Some(validator) = &mut validator {
+        if !validator.has_capacity(amount) {
+            msg!("Validator collateral cap is {} lamports", validator.max_collateral);
+            return Err(ErrorCode::ValidatorCapExceeded.into());
+        }
+
+        validator.collateral_amount += amount;
+        validator.exit(&crate::ID)?;
+    }
+
+    if !with_merge && ctx.accounts.collateral_user.key() != ctx.accounts.user.key() {
+        return Err(ErrorCode::WrongData.into());
+    }
+
+    if with_merge {
+        let collateral = &ctx.accounts.collateral;
+        if collateral.user != ctx.accounts.user.key() || !collateral.is_native {
+            return Err(ErrorCode::InvalidStakeAccount.into());
+        }
+
+        // the deposit can be merged only into the fully active stake of the same validator
+        let delegated_stake = Account::<stake::StakeAccount>::try_from(&ctx.accounts.delegated_stake)?;
+        let destination = delegated_stake.delegation().ok_or(ErrorCode::InvalidStakeAccount)?;
+
+        if destination.voter_pubkey != delegation.voter_pubkey
+            || delegated_stake.key() == ctx.accounts.source_stake.key()
+        {
+            return Err(ErrorCode::InvalidStakeAccount.into());
+        }
+
+        if !stake::is_fully_active(&destination, ctx.accounts.clock.epoch, &ctx.accounts.stake_history) {
+            return Err(ErrorCode::StakeNotActive.into());
+        }
+    } else if ctx.accounts.collateral.user != Pubkey::default()
+        || (amount == delegation.stake && ctx.accounts.delegated_stake.key() == ctx.accounts.split_stake.key())
         || (amount != delegation.stake && ctx.accounts.delegated_stake.key() == ctx.accounts.source_stake.key())
     {
         return Err(ErrorCode::InvalidStakeAccount.into());
     }
 
     if pool.deposit_fee > 0 {
-        let fee = amount.saturating_div(1000).saturating_mul(pool.deposit_fee as u64);
+        let fee = calc_fee(amount, pool.deposit_fee);
+        let referral_fee = pay_referral_fee(
+            ctx.remaining_accounts,
+            pool.key(),
+            pool.referral_share,
+            ctx.accounts.authority.key(),
+            ctx.accounts.fee_payer.to_account_info(),
+            ctx.accounts.system_program.to_account_info(),
+            fee,
+            ctx.accounts.clock.unix_timestamp,
+        )?;
+        let fee = fee - referral_fee;
         msg!("Transfer deposit fee: {} lamports", fee);
 
         system_program::transfer(
@@ -41,19 +152,28 @@ pub fn handle(ctx: Context<DepositStake>, amount: u64) -> Result<()> {
                 ctx.accounts.system_program.to_account_info(),
                 system_program::Transfer {
                     from: ctx.accounts.fee_payer.to_account_info(),
-                    to: ctx.accounts.fee_receiver.to_account_info(),
+                    to: ctx.accounts.treasury.to_account_info(),
                 },
             ),
             fee,
         )
         .map_err(|_| ErrorCode::InsufficientFunds)?;
+
+        record_fee(
+            &mut ctx.accounts.treasury,
+            FeeType::Deposit,
+            FeeMode::Sol,
+            fee,
+            ctx.accounts.clock.unix_timestamp,
+        );
     }
 
     let pool_key = pool.key();
+    let pool_authority_seeds = [pool_key.as_ref(), &[pool.authority_bump]];
     let clock = &ctx.accounts.clock;
 
-    let stake_account = if amount < delegation.stake {
-        // Split new stake from existing stake
+    let stake_account = if let Some(split_lamports) = split_lamports {
+        // Split new stake from existing stake, the rent-exempt reserve of the new account is taken from the delegation
         stake::split(
             CpiContext::new(
                 ctx.accounts.stake_program.to_account_info(),
@@ -64,13 +184,21 @@ pub fn handle(ctx: Context<DepositStake>, amount: u64) -> Result<()> {
                     system_program: ctx.accounts.system_program.to_account_info(),
                 },
             ),
-            amount,
+            split_lamports,
         )?;
         ctx.accounts.split_stake.to_account_info()
     } else {
         ctx.accounts.source_stake.to_account_info()
     };
 
+    let lamports = stake_account.lamports();
+    msg!(
+        "Deposited stake: {} lamports delegated, {} lamports in total, {} lamports of rent-exempt reserve",
+        amount,
+        lamports,
+        rent_exempt_reserve
+    );
+
     // Authorize to `withdraw` the stake for the program
     stake::authorize(
         CpiContext::new(
@@ -106,6 +234,7 @@ pub fn handle(ctx: Context<DepositStake>, amount: u64) -> Result<()> {
 
     if user.wallet != ctx.accounts.authority.key() {
         user.wallet = ctx.accounts.authority.key();
+        user.pool = pool_key;
         user.rate = 0;
         user.is_blocked = false;
 
@@ -121,33 +250,88 @@ pub fn handle(ctx: Context<DepositStake>, amount: u64) -> Result<()> {
 
     user.rate += amount;
 
-    collateral.user = ctx.accounts.user.key();
-    collateral.pool = pool_key;
-    collateral.stake_source = ctx.accounts.source_stake.key();
-    collateral.delegated_stake = stake_account.key();
-    collateral.delegation_stake = amount;
-    collateral.amount = 0;
-    collateral.liquidated_amount = 0;
-    collateral.created_at = clock.unix_timestamp;
-    collateral.creation_epoch = clock.epoch;
-    collateral.bump = ctx.bumps["collateral"];
-    collateral.is_native = true;
+    if with_merge {
+        stake::merge(CpiContext::new_with_signer(
+            ctx.accounts.stake_program.to_account_info(),
+            stake::Merge {
+                destination_stake: ctx.accounts.delegated_stake.to_account_info(),
+                source_stake: stake_account,
+                authority: ctx.accounts.pool_authority.to_account_info(),
+                stake_history: ctx.accounts.stake_history.to_account_info(),
+                clock: clock.to_account_info(),
+                system_program: ctx.accounts.system_program.to_account_info(),
+            },
+            &[&pool_authority_seeds],
+        ))?;
+
+        // only the delegation of the deposited stake is merged into the collateral delegation
+        let refund = lamports - amount;
+        if refund > 0 {
+            msg!("Refund {} undelegated lamports of the deposited stake", refund);
+
+            stake::withdraw(
+                CpiContext::new_with_signer(
+                    ctx.accounts.stake_program.to_account_info(),
+                    stake::Withdraw {
+                        stake: ctx.accounts.delegated_stake.to_account_info(),
+                        to: ctx.accounts.authority.to_account_info(),
+                        authority: ctx.accounts.pool_authority.to_account_info(),
+                        clock: clock.to_account_info(),
+                        stake_history: ctx.accounts.stake_history.to_account_info(),
+                    },
+                    &[&pool_authority_seeds],
+                ),
+                refund,
+            )?;
+        }
+
+        collateral.settle_storage_fee(pool.storage_fee, clock.epoch);
+        collateral.delegation_stake += amount;
+    } else {
+        collateral.user = user.key();
+        collateral.pool = pool_key;
+        collateral.stake_source = ctx.accounts.source_stake.key();
+        collateral.delegated_stake = stake_account.key();
+        collateral.delegation_stake = amount;
+        collateral.amount = 0;
+        collateral.liquidated_amount = 0;
+        collateral.created_at = clock.unix_timestamp;
+        collateral.creation_epoch = clock.epoch;
+        collateral.bump = ctx.bumps["collateral"];
+        collateral.is_native = true;
+        collateral.fee_paid_through_epoch = clock.epoch;
+        collateral.accrued_storage_fee = 0;
+        collateral.pending_vote_account = None;
+        collateral.redelegation_excess = 0;
+
+        pool.collaterals_amount = pool.collaterals_amount.saturating_add(1);
+    }
 
     pool.deposit_amount = pool.deposit_amount.saturating_add(amount);
-    pool.collaterals_amount = pool.collaterals_amount.saturating_add(1);
+
+    let oracle = &mut ctx.accounts.oracle;
+    oracle.set_rate(user.key(), user.rate);
+    oracle.upsert(QueueMember {
+        collateral: collateral.key(),
+        user: user.key(),
+        amount: collateral.get_rest_amount(),
+        rate: user.rate,
+    });
 
     emit!(DepositStakeEvent {
         pool: pool.key(),
         collateral: collateral.key(),
         delegation_stake: collateral.delegation_stake,
         timestamp: clock.unix_timestamp,
-        amount: delegation.stake,
+        amount,
+        lamports,
     });
 
     Ok(())
 }
 
 #[derive(Accounts)]
+#[instruction(amount: u64, with_merge: bool)]
 pub struct DepositStake<'info> {
     #[account(mut, constraint = pool.stake_source == stake_program.key())]
     pub pool: Box<Account<'info, Pool>>,
@@ -158,27 +342,50 @@ pub struct DepositStake<'info> {
 
     #[account(
         init_if_needed,
-        seeds = [User::SEED, authority.key().as_ref()],
+        seeds = [User::SEED, pool.key().as_ref(), authority.key().as_ref()],
         bump,
         payer = authority,
         space = User::SIZE,
     )]
     pub user: Box<Account<'info, User>>,
 
+    /// CHECK: user account the collateral address is derived from
+    pub collateral_user: AccountInfo<'info>,
+
     #[account(
-        init,
-        seeds = [Collateral::SEED, user.key().as_ref(), delegated_stake.key().as_ref()],
+        init_if_needed,
+        seeds = [Collateral::SEED, collateral_user.key().as_ref(), delegated_stake.key().as_ref()],
         bump,
         payer = authority,
         space = Collateral::SIZE,
     )]
     pub collateral: Account<'info, Collateral>,
 
+    #[account(mut, seeds = [Oracle::SEED], bump)]
+    pub oracle: Box<Account<'info, Oracle>>,
+
     #[account(mut)]
     pub source_stake: Account<'info, stake::StakeAccount>,
 
-    /// CHECK:
-    #[account(mut, constraint = delegated_stake.key() == source_stake.key() || delegated_stake.key() == split_stake.key())]
+    /// CHECK: validator entry of the stake's vote account, may be uninitialized
+    #[account(
+        mut,
+        seeds = [
+            Validator::SEED,
+            pool.key().as_ref(),
+            source_stake.delegation().map(|d| d.voter_pubkey).unwrap_or_default().as_ref(),
+        ],
+        bump,
+    )]
+    pub validator: AccountInfo<'info>,
+
+    /// CHECK: stake account of the new collateral or of the existing one to merge the deposit into
+    #[account(
+        mut,
+        constraint = with_merge
+            || delegated_stake.key() == source_stake.key()
+            || delegated_stake.key() == split_stake.key()
+    )]
     pub delegated_stake: AccountInfo<'info>,
 
     /// CHECK:
@@ -191,11 +398,11 @@ pub struct DepositStake<'info> {
     #[account(mut)]
     pub fee_payer: Signer<'info>,
 
-    /// CHECK: no needs to check, only for transfer
-    #[account(mut, address = pool.fee_receiver)]
-    pub fee_receiver: AccountInfo<'info>,
+    #[account(mut, seeds = [Treasury::SEED, pool.key().as_ref()], bump = treasury.bump)]
+    pub treasury: Box<Account<'info, Treasury>>,
 
     pub clock: Sysvar<'info, Clock>,
+    pub stake_history: Sysvar<'info, StakeHistory>,
     pub stake_program: Program<'info, stake::Stake>,
     pub system_program: Program<'info, System>,
 }
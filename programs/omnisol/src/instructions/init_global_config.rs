@@ -0,0 +1,36 @@
+use anchor_lang::prelude::*;
+
+use crate::{program::Omnisol, state::GlobalConfig};
+
+/// The program upgrade authority can init global config and become the admin.
+pub fn handle(ctx: Context<InitGlobalConfig>) -> Result<()> {
+    let global_config = &mut ctx.accounts.global_config;
+
+    global_config.admin = ctx.accounts.authority.key();
+    global_config.pending_admin = None;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct InitGlobalConfig<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        init,
+        seeds = [GlobalConfig::SEED],
+        bump,
+        payer = authority,
+        space = GlobalConfig::SIZE
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, Omnisol>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()))]
+    pub program_data: Account<'info, ProgramData>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -1,10 +1,9 @@
-use std::str::FromStr;
-
 use anchor_lang::prelude::*;
 
-use crate::state::{Oracle, ADMIN};
+use crate::state::{GlobalConfig, Oracle};
 
 /// The admin can init oracle.
+/// The account is created with the CPI size limit and grows to the full size on the first `update_oracle_info`.
 pub fn handle(ctx: Context<InitOracle>) -> Result<()> {
     let oracle = &mut ctx.accounts.oracle;
 
@@ -15,18 +14,22 @@ pub fn handle(ctx: Context<InitOracle>) -> Result<()> {
 
 #[derive(Accounts)]
 pub struct InitOracle<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
     #[account(
-        mut,
-        address = Pubkey::from_str(ADMIN).unwrap()
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
     )]
-    pub authority: Signer<'info>,
+    pub global_config: Box<Account<'info, GlobalConfig>>,
 
     #[account(
         init,
         seeds = [Oracle::SEED],
         bump,
         payer = authority,
-        space = Oracle::SIZE
+        space = Oracle::INIT_SIZE
     )]
     pub oracle: Box<Account<'info, Oracle>>,
 
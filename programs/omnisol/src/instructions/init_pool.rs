@@ -2,11 +2,12 @@ use anchor_lang::{prelude::*, solana_program::native_token::LAMPORTS_PER_SOL};
 use anchor_spl::token;
 
 use crate::{
-    state::{Manager, Pool, MINT_AUTHORITY_SEED},
+    state::{FeeMode, Manager, Pool, Treasury, ValidatorPolicy, MINT_AUTHORITY_SEED},
     ErrorCode,
 };
 
-/// The manager can initialize pool for special liquidity token or native stake program.
+/// The manager with the pools permission can initialize pool for special liquidity token or native stake program.
+/// The pool treasury, that collects SOL fees, is initialized along with the pool.
 pub fn handle(ctx: Context<InitPool>) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
     let mint_authority = ctx.accounts.mint_authority.key;
@@ -23,9 +24,28 @@ pub fn handle(ctx: Context<InitPool>) -> Result<()> {
     pool.fee_receiver = ctx.accounts.fee_receiver.key();
     pool.authority_bump = ctx.bumps["pool_authority"];
     pool.min_deposit = LAMPORTS_PER_SOL / 2;
+    pool.min_mint = 0;
+    pool.min_burn = 0;
+    pool.fee_mode = FeeMode::Sol;
+    pool.fee_recipients = vec![];
+    pool.referral_share = 0;
+    pool.validator_policy = ValidatorPolicy::Any;
     pool.deposit_amount = 0;
     pool.is_active = true;
 
+    let treasury = &mut ctx.accounts.treasury;
+    treasury.pool = pool.key();
+    treasury.deposit_fees = 0;
+    treasury.mint_fees = 0;
+    treasury.withdraw_fees = 0;
+    treasury.storage_fees = 0;
+    treasury.liquidation_fees = 0;
+    treasury.swept_amount = 0;
+    treasury.bump = ctx.bumps["treasury"];
+    treasury.liquidation_fee_balance = 0;
+    treasury.in_kind_liquidation_fee_balance = 0;
+    treasury.escrowed_liquidation_fees = 0;
+
     Ok(())
 }
 
@@ -41,6 +61,15 @@ pub struct InitPool<'info> {
     #[account(seeds = [pool.key().as_ref()], bump)]
     pub pool_authority: AccountInfo<'info>,
 
+    #[account(
+        init,
+        seeds = [Treasury::SEED, pool.key().as_ref()],
+        bump,
+        payer = authority,
+        space = Treasury::SIZE,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
     /// CHECK: no needs to check, only for clarifying
     #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
     pub mint_authority: AccountInfo<'info>,
@@ -48,7 +77,12 @@ pub struct InitPool<'info> {
     /// CHECK: Address of LP token or native stake program
     pub stake_source: AccountInfo<'info>,
 
-    #[account(mut, seeds = [Manager::SEED, authority.key().as_ref()], bump)]
+    #[account(
+        mut,
+        seeds = [Manager::SEED, authority.key().as_ref()],
+        bump,
+        constraint = manager.has_permission(Manager::POOLS)
+    )]
     pub manager: Box<Account<'info, Manager>>,
 
     #[account(mut)]
@@ -1,18 +1,39 @@
 use anchor_lang::{prelude::*, solana_program::program::invoke_signed};
-use anchor_spl::token;
-use spl_stake_pool::instruction::{withdraw_sol, withdraw_stake};
+use anchor_spl::{associated_token::get_associated_token_address, token};
+use spl_stake_pool::instruction::{deposit_stake, withdraw_sol, withdraw_stake};
 
 use crate::{
     events::*,
-    state::{Collateral, Liquidator, Oracle, Pool, User, WithdrawInfo},
+    state::{
+        Collateral, FeeType, LiquidationRoute, Liquidator, Oracle, Pool, Treasury, User, Validator, Whitelist,
+        WithdrawInfo, WithdrawMode,
+    },
     utils,
-    utils::{stake, unstake_it},
+    utils::{
+        fee::record_fee,
+        stake,
+        stake_pool::{get_lamports_value, get_pool_tokens, get_stake_pool},
+        unstake_it,
+        validator::load_validator,
+    },
     ErrorCode,
 };
 
 /// The liquidator can get collateral from priority queue and withdraw SOL from it to process withdraw request.
 /// The user, that made this request, instantly gets his SOL.
-pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>, amount: u64) -> Result<()> {
+/// Caller provides [route] of native stake liquidation: unstake.it or a whitelisted SPL stake pool,
+/// which accounts are passed as remaining accounts after the split stake.
+/// Liquidation reverts if the user receives less than the proportional part of the request's minimal lamports out.
+/// The received lamports are measured by the balance of the user wallet, so it can't be the liquidator,
+/// that pays for the liquidation.
+/// The [amount] is in lamports, LP token collateral is liquidated by the exchange rate of its stake pool,
+/// so the whitelist is passed after the stake pool accounts.
+/// Liquidated native stake is released from the cap of its validator entry.
+pub fn handle<'info>(
+    ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>,
+    amount: u64,
+    route: LiquidationRoute,
+) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
 
     if !pool.is_active {
@@ -33,19 +54,50 @@ pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>
     let collateral = &mut ctx.accounts.collateral;
     let oracle = &mut ctx.accounts.oracle;
 
-    let rest_amount = collateral.delegation_stake - collateral.liquidated_amount;
+    if collateral.pending_vote_account.is_some() {
+        msg!("Collateral stake is being redelegated");
+        return Err(ErrorCode::StakeNotActive.into());
+    }
+
+    let rest_amount = collateral.get_rest_amount();
+
+    // stake pool of the LP token collateral, that values it in lamports
+    let stake_pool = if collateral.is_native {
+        None
+    } else {
+        let whitelist_info = ctx.remaining_accounts.get(10).ok_or(ErrorCode::WrongData)?;
+        let stake_pool_info = ctx.remaining_accounts.get(1).ok_or(ErrorCode::WrongData)?;
+
+        let whitelist = Account::<Whitelist>::try_from(whitelist_info)?;
+        if whitelist.mint != collateral.stake_source {
+            return Err(ErrorCode::InvalidToken.into());
+        }
+
+        Some((get_stake_pool(stake_pool_info, &whitelist)?, whitelist.pool_program))
+    };
 
-    if amount == 0 || amount > rest_amount {
+    // rest value in lamports and the liquidated amount in units of the collateral
+    let (rest_value, stake_amount) = match &stake_pool {
+        None => (rest_amount, amount),
+        Some((stake_pool, _)) => (
+            get_lamports_value(stake_pool, rest_amount)?,
+            get_pool_tokens(stake_pool, amount)?.min(rest_amount),
+        ),
+    };
+
+    if amount == 0 || amount > rest_value {
         return Err(ErrorCode::InsufficientAmount.into());
     }
 
     let mut queue_member = oracle
         .priority_queue
-        .iter_mut()
-        .find(|queue_member| queue_member.collateral == collateral.key() && queue_member.amount == rest_amount)
+        .iter()
+        .find(|queue_member| queue_member.collateral == collateral.key())
+        .cloned()
         .ok_or::<Error>(ErrorCode::WrongData.into())?;
 
-    queue_member.amount -= amount;
+    queue_member.amount = rest_value - amount;
+    oracle.upsert(queue_member);
 
     let pool_key = pool.key();
     let pool_authority_seeds = [pool_key.as_ref(), &[pool.authority_bump]];
@@ -53,16 +105,41 @@ pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>
 
     let withdraw_info = &mut ctx.accounts.withdraw_info;
 
+    if withdraw_info.mode != WithdrawMode::Instant {
+        return Err(ErrorCode::InvalidWithdrawMode.into());
+    }
+
     if amount > withdraw_info.amount {
         return Err(ErrorCode::InsufficientAmount.into());
     }
 
+    let min_lamports_out = withdraw_info.get_min_lamports_out(amount);
+    let user_lamports_before = ctx.accounts.user_wallet.lamports();
+
     if collateral.is_native {
         if collateral.delegated_stake != ctx.accounts.source_stake.key() {
             return Err(ErrorCode::InvalidStakeAccount.into());
         }
 
-        let split_stake = ctx.remaining_accounts.get(0).expect("Expect #0 account");
+        let delegation = Account::<stake::StakeAccount>::try_from(&ctx.accounts.source_stake)?
+            .delegation()
+            .ok_or(ErrorCode::InvalidStakeAccount)?;
+
+        let (validator_key, _) = Pubkey::find_program_address(
+            &[Validator::SEED, pool_key.as_ref(), delegation.voter_pubkey.as_ref()],
+            &crate::ID,
+        );
+
+        if ctx.accounts.validator.key() != validator_key {
+            return Err(ErrorCode::WrongData.into());
+        }
+
+        if let Some(mut validator) = load_validator(&ctx.accounts.validator)? {
+            validator.collateral_amount = validator.collateral_amount.saturating_sub(amount);
+            validator.exit(&crate::ID)?;
+        }
+
+        let split_stake = ctx.remaining_accounts.get(0).ok_or(ErrorCode::WrongData)?;
 
         let stake_account = if amount < rest_amount {
             // Split new stake from existing stake
@@ -85,43 +162,67 @@ pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>
             ctx.accounts.source_stake.to_account_info()
         };
 
-        unstake_it::unstake(
-            CpiContext::new_with_signer(
-                ctx.accounts.unstake_it_program.to_account_info(),
-                unstake_it::Unstake {
-                    payer: ctx.accounts.authority.to_account_info(),
-                    unstaker: ctx.accounts.pool_authority.to_account_info(),
+        match route {
+            LiquidationRoute::UnstakeIt => {
+                unstake_it::unstake(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.unstake_it_program.to_account_info(),
+                        unstake_it::Unstake {
+                            payer: ctx.accounts.authority.to_account_info(),
+                            unstaker: ctx.accounts.pool_authority.to_account_info(),
+                            stake_account,
+                            destination: ctx.accounts.user_wallet.to_account_info(),
+                            pool_account: ctx.accounts.pool_account.to_account_info(),
+                            pool_sol_reserves: ctx.accounts.sol_reserves.to_account_info(),
+                            fee_account: ctx.accounts.fee_account.to_account_info(),
+                            stake_account_record_account: ctx.accounts.stake_account_record.to_account_info(),
+                            protocol_fee_account: ctx.accounts.protocol_fee.to_account_info(),
+                            protocol_fee_destination: ctx.accounts.protocol_fee_destination.to_account_info(),
+                            clock: ctx.accounts.clock.to_account_info(),
+                            stake_program: ctx.accounts.stake_program.to_account_info(),
+                            system_program: ctx.accounts.system_program.to_account_info(),
+                        },
+                        &[&pool_authority_seeds],
+                    ),
+                    ctx.accounts.unstake_it_program.key(),
+                )?;
+            }
+            LiquidationRoute::StakePool => {
+                liquidate_to_stake_pool(
+                    &ctx.remaining_accounts[1..],
                     stake_account,
-                    destination: ctx.accounts.user_wallet.to_account_info(),
-                    pool_account: ctx.accounts.pool_account.to_account_info(),
-                    pool_sol_reserves: ctx.accounts.sol_reserves.to_account_info(),
-                    fee_account: ctx.accounts.fee_account.to_account_info(),
-                    stake_account_record_account: ctx.accounts.stake_account_record.to_account_info(),
-                    protocol_fee_account: ctx.accounts.protocol_fee.to_account_info(),
-                    protocol_fee_destination: ctx.accounts.protocol_fee_destination.to_account_info(),
-                    clock: ctx.accounts.clock.to_account_info(),
-                    stake_program: ctx.accounts.stake_program.to_account_info(),
-                    system_program: ctx.accounts.system_program.to_account_info(),
-                },
-                &[&pool_authority_seeds],
-            ),
-            ctx.accounts.unstake_it_program.key(),
-        )?;
+                    ctx.accounts.pool_authority.to_account_info(),
+                    ctx.accounts.user_wallet.to_account_info(),
+                    ctx.accounts.clock.to_account_info(),
+                    ctx.accounts.token_program.to_account_info(),
+                    ctx.accounts.stake_program.to_account_info(),
+                    &[&pool_authority_seeds],
+                )?;
+            }
+        }
     } else {
         if collateral.stake_source != ctx.accounts.source_stake.key() {
             return Err(ErrorCode::InvalidToken.into());
         }
 
-        let staking_pool_program = ctx.remaining_accounts.get(0).expect("Expect #0 account");
-        let stake_pool = ctx.remaining_accounts.get(1).expect("Expect #1 account");
-        let stake_pool_withdraw_authority = ctx.remaining_accounts.get(2).expect("Expect #2 account");
-        let reserve_stake_account = ctx.remaining_accounts.get(3).expect("Expect #3 account");
-        let manager_fee_account = ctx.remaining_accounts.get(4).expect("Expect #4 account");
-        let stake_history = ctx.remaining_accounts.get(5).expect("Expect #5 account");
-        let validator_list_storage = ctx.remaining_accounts.get(6).expect("Expect #6 account");
-        let stake_to_split = ctx.remaining_accounts.get(7).expect("Expect #7 account");
-        let stake_to_receive = ctx.remaining_accounts.get(8).expect("Expect #8 account");
-        let pool_token_account = ctx.remaining_accounts.get(9).expect("Expect #9 account");
+        let account = |index: usize| ctx.remaining_accounts.get(index).ok_or(ErrorCode::WrongData);
+
+        let staking_pool_program = account(0)?;
+
+        // LP tokens can be withdrawn only from the whitelisted stake pool program
+        if !matches!(&stake_pool, Some((_, pool_program)) if staking_pool_program.key() == *pool_program) {
+            return Err(ErrorCode::InvalidStakePool.into());
+        }
+
+        let stake_pool = account(1)?;
+        let stake_pool_withdraw_authority = account(2)?;
+        let reserve_stake_account = account(3)?;
+        let manager_fee_account = account(4)?;
+        let stake_history = account(5)?;
+        let validator_list_storage = account(6)?;
+        let stake_to_split = account(7)?;
+        let stake_to_receive = account(8)?;
+        let pool_token_account = account(9)?;
 
         let ix = withdraw_sol(
             staking_pool_program.key,
@@ -134,7 +235,7 @@ pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>
             manager_fee_account.key,
             ctx.accounts.source_stake.key,
             ctx.accounts.token_program.key,
-            amount,
+            stake_amount,
         );
 
         let account_infos = vec![
@@ -166,7 +267,7 @@ pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>
                 manager_fee_account.key,
                 ctx.accounts.source_stake.key,
                 ctx.accounts.token_program.key,
-                amount,
+                stake_amount,
             );
 
             let account_infos = vec![
@@ -212,9 +313,37 @@ pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>
         }
     }
 
-    collateral.liquidated_amount += amount;
+    let lamports_out = ctx.accounts.user_wallet.lamports().saturating_sub(user_lamports_before);
+
+    if lamports_out < min_lamports_out {
+        msg!(
+            "Received {} lamports, expected at least {}",
+            lamports_out,
+            min_lamports_out
+        );
+        return Err(ErrorCode::SlippageExceeded.into());
+    }
+
+    // the owner pays the storage fee for the whole collateral up to the liquidation
+    collateral.settle_storage_fee(pool.storage_fee, clock.epoch);
+    collateral.liquidated_amount += stake_amount;
+    withdraw_info.min_lamports_out -= min_lamports_out;
+    let fee = withdraw_info.get_fee(amount);
+    withdraw_info.fee -= fee;
     withdraw_info.amount -= amount;
 
+    // the escrowed liquidation fee of the fulfilled part is earned
+    ctx.accounts
+        .treasury
+        .release_liquidation_fee(withdraw_info.fee_mode, fee);
+    record_fee(
+        &mut ctx.accounts.treasury,
+        FeeType::Liquidation,
+        withdraw_info.fee_mode,
+        fee,
+        clock.unix_timestamp,
+    );
+
     if withdraw_info.amount == 0 {
         // close the withdraw_info account
         utils::close(
@@ -227,10 +356,15 @@ pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>
 
     pool.deposit_amount = pool
         .deposit_amount
-        .checked_sub(amount)
+        .checked_sub(stake_amount)
         .ok_or(ErrorCode::InsufficientAmount)?;
 
-    if collateral.delegation_stake == collateral.liquidated_amount && collateral.amount == collateral.delegation_stake {
+    let delegation_value = match &stake_pool {
+        None => collateral.delegation_stake,
+        Some((stake_pool, _)) => get_lamports_value(stake_pool, collateral.delegation_stake)?,
+    };
+
+    if collateral.is_closable(delegation_value) {
         // close the collateral account
         utils::close(
             collateral.to_account_info(),
@@ -245,13 +379,134 @@ pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>
         authority: ctx.accounts.authority.key(),
         collateral: collateral.key(),
         amount,
-        rest_amount: rest_amount - amount,
+        rest_amount: rest_value - amount,
+        route,
+        lamports_out,
         timestamp: clock.unix_timestamp,
     });
 
     Ok(())
 }
 
+/// Deposits the native [stake_account] into the whitelisted SPL stake pool
+/// and withdraws the received LP tokens as SOL to the [destination].
+/// Accounts: whitelist, stake pool program, stake pool, validator list, deposit authority, withdraw authority,
+/// validator stake account, reserve stake account, manager fee account, LP token mint,
+/// LP token account of the pool authority and stake history.
+fn liquidate_to_stake_pool<'info>(
+    accounts: &[AccountInfo<'info>],
+    stake_account: AccountInfo<'info>,
+    pool_authority: AccountInfo<'info>,
+    destination: AccountInfo<'info>,
+    clock: AccountInfo<'info>,
+    token_program: AccountInfo<'info>,
+    stake_program: AccountInfo<'info>,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    let account = |index: usize| accounts.get(index).ok_or(ErrorCode::WrongData);
+
+    let whitelist = Account::<Whitelist>::try_from(account(0)?)?;
+    let staking_pool_program = account(1)?;
+    let stake_pool = account(2)?;
+    let validator_list_storage = account(3)?;
+    let stake_pool_deposit_authority = account(4)?;
+    let stake_pool_withdraw_authority = account(5)?;
+    let validator_stake_account = account(6)?;
+    let reserve_stake_account = account(7)?;
+    let manager_fee_account = account(8)?;
+    let pool_mint = account(9)?;
+    let pool_token_account = account(10)?;
+    let stake_history = account(11)?;
+
+    if staking_pool_program.key() != whitelist.pool_program || pool_mint.key() != whitelist.mint {
+        return Err(ErrorCode::InvalidStakePool.into());
+    }
+
+    get_stake_pool(stake_pool, &whitelist)?;
+
+    if pool_token_account.key() != get_associated_token_address(pool_authority.key, &whitelist.mint) {
+        return Err(ErrorCode::InvalidToken.into());
+    }
+
+    let pool_tokens_before = token::accessor::amount(pool_token_account)?;
+
+    // the pool authority is the referrer as well, so the referral fee is withdrawn too
+    let ixs = deposit_stake(
+        staking_pool_program.key,
+        stake_pool.key,
+        validator_list_storage.key,
+        stake_pool_withdraw_authority.key,
+        stake_account.key,
+        pool_authority.key,
+        validator_stake_account.key,
+        reserve_stake_account.key,
+        pool_token_account.key,
+        manager_fee_account.key,
+        pool_token_account.key,
+        pool_mint.key,
+        token_program.key,
+    );
+
+    let account_infos = vec![
+        staking_pool_program.to_account_info(),
+        stake_pool.to_account_info(),
+        validator_list_storage.to_account_info(),
+        stake_pool_deposit_authority.to_account_info(),
+        stake_pool_withdraw_authority.to_account_info(),
+        stake_account.to_account_info(),
+        pool_authority.to_account_info(),
+        validator_stake_account.to_account_info(),
+        reserve_stake_account.to_account_info(),
+        pool_token_account.to_account_info(),
+        manager_fee_account.to_account_info(),
+        pool_mint.to_account_info(),
+        clock.to_account_info(),
+        stake_history.to_account_info(),
+        token_program.to_account_info(),
+        stake_program.to_account_info(),
+    ];
+
+    for ix in &ixs {
+        invoke_signed(ix, &account_infos, signer_seeds)?;
+    }
+
+    let pool_tokens = token::accessor::amount(pool_token_account)?
+        .checked_sub(pool_tokens_before)
+        .ok_or(ErrorCode::InsufficientAmount)?;
+
+    let ix = withdraw_sol(
+        staking_pool_program.key,
+        stake_pool.key,
+        stake_pool_withdraw_authority.key,
+        pool_authority.key,
+        pool_token_account.key,
+        reserve_stake_account.key,
+        destination.key,
+        manager_fee_account.key,
+        pool_mint.key,
+        token_program.key,
+        pool_tokens,
+    );
+
+    let account_infos = vec![
+        staking_pool_program.to_account_info(),
+        stake_pool.to_account_info(),
+        stake_pool_withdraw_authority.to_account_info(),
+        pool_authority,
+        pool_token_account.to_account_info(),
+        reserve_stake_account.to_account_info(),
+        destination,
+        manager_fee_account.to_account_info(),
+        pool_mint.to_account_info(),
+        clock,
+        stake_history.to_account_info(),
+        stake_program,
+        token_program,
+    ];
+
+    invoke_signed(&ix, &account_infos, signer_seeds).map_err(Into::into)
+}
+
 #[derive(Accounts)]
 pub struct LiquidateCollateral<'info> {
     #[account(mut, address = collateral.pool)]
@@ -261,22 +516,10 @@ pub struct LiquidateCollateral<'info> {
     #[account(seeds = [pool.key().as_ref()], bump = pool.authority_bump)]
     pub pool_authority: AccountInfo<'info>,
 
-    #[account(
-        mut,
-        seeds = [
-            Collateral::SEED,
-            collateral_owner.key().as_ref(),
-            source_stake.key().as_ref()
-        ],
-        bump,
-    )]
+    #[account(mut, constraint = collateral.user == collateral_owner.key())]
     pub collateral: Account<'info, Collateral>,
 
-    #[account(
-        mut,
-        seeds = [User::SEED, collateral_owner_wallet.key().as_ref()],
-        bump,
-    )]
+    #[account(mut, constraint = collateral_owner.wallet == collateral_owner_wallet.key())]
     pub collateral_owner: Box<Account<'info, User>>,
 
     /// CHECK:
@@ -284,12 +527,16 @@ pub struct LiquidateCollateral<'info> {
     pub collateral_owner_wallet: AccountInfo<'info>,
 
     /// CHECK:
-    #[account(mut, address = withdraw_info.authority)]
+    #[account(
+        mut,
+        address = withdraw_info.authority,
+        constraint = user_wallet.key() != authority.key()
+    )]
     pub user_wallet: AccountInfo<'info>,
 
     #[account(
         mut,
-        seeds = [User::SEED, user_wallet.key().as_ref()],
+        seeds = [User::SEED, withdraw_info.pool.as_ref(), user_wallet.key().as_ref()],
         bump,
     )]
     pub user: Box<Account<'info, User>>,
@@ -298,13 +545,16 @@ pub struct LiquidateCollateral<'info> {
         mut,
         seeds = [
             WithdrawInfo::SEED,
-            user_wallet.key().as_ref(),
+            user.key().as_ref(),
             user.get_index().to_le_bytes().as_ref(),
         ],
         bump,
     )]
     pub withdraw_info: Box<Account<'info, WithdrawInfo>>,
 
+    #[account(mut, seeds = [Treasury::SEED, withdraw_info.pool.as_ref()], bump = treasury.bump)]
+    pub treasury: Box<Account<'info, Treasury>>,
+
     #[account(
         mut,
         seeds = [Oracle::SEED],
@@ -316,6 +566,10 @@ pub struct LiquidateCollateral<'info> {
     #[account(mut)]
     pub source_stake: AccountInfo<'info>,
 
+    /// CHECK: validator entry of the native collateral's vote account, may be uninitialized, unused for LP tokens
+    #[account(mut)]
+    pub validator: AccountInfo<'info>,
+
     #[account(
         seeds = [Liquidator::SEED, authority.key().as_ref()],
         bump,
@@ -0,0 +1,47 @@
+use anchor_lang::{prelude::*, Discriminator};
+
+use crate::{
+    state::{Collateral, LegacyCollateral},
+    utils,
+};
+
+/// Anyone can migrate collateral account created before the storage fee was settled per epoch.
+/// The account is reallocated, the caller pays the rent difference.
+/// The storage fee of the migrated collateral is accrued since its creation epoch.
+pub fn handle(ctx: Context<MigrateCollateral>) -> Result<()> {
+    migrate_legacy_collateral(
+        &ctx.accounts.collateral,
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+    )
+}
+
+/// Rewrites the legacy collateral account with the current layout.
+pub fn migrate_legacy_collateral<'info>(
+    collateral_info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    let legacy_collateral: LegacyCollateral =
+        utils::load_legacy(collateral_info, LegacyCollateral::SIZE, Collateral::discriminator())?;
+
+    utils::realloc(collateral_info, payer, system_program, Collateral::SIZE)?;
+
+    let collateral = Collateral::from(legacy_collateral);
+    let mut data = collateral_info.try_borrow_mut_data()?;
+    collateral.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateCollateral<'info> {
+    /// CHECK: Collateral account of the previous layout, will be deserialized manually
+    #[account(mut)]
+    pub collateral: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,54 @@
+use anchor_lang::{prelude::*, Discriminator};
+
+use crate::{
+    state::{legacy_fee_to_bps, GlobalConfig, LegacyLiquidationFee, LiquidationFee},
+    utils, ErrorCode,
+};
+
+/// The admin can migrate liquidation fee account created before fee recipients were added.
+/// The account is reallocated and the fee is converted from 1/1000 to basis points,
+/// the admin pays the rent difference.
+pub fn handle(ctx: Context<MigrateLiquidationFee>) -> Result<()> {
+    let liquidation_fee_info = ctx.accounts.liquidation_fee.to_account_info();
+    let legacy_liquidation_fee: LegacyLiquidationFee = utils::load_legacy(
+        &liquidation_fee_info,
+        LegacyLiquidationFee::SIZE,
+        LiquidationFee::discriminator(),
+    )?;
+
+    utils::realloc(
+        &liquidation_fee_info,
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        LiquidationFee::SIZE,
+    )?;
+
+    let liquidation_fee = LiquidationFee {
+        fee_receiver: legacy_liquidation_fee.fee_receiver,
+        fee: legacy_fee_to_bps(legacy_liquidation_fee.fee).ok_or(ErrorCode::TypeOverflow)?,
+        fee_recipients: vec![],
+    };
+    let mut data = liquidation_fee_info.try_borrow_mut_data()?;
+    liquidation_fee.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateLiquidationFee<'info> {
+    /// CHECK: Liquidation fee account of the previous layout, will be deserialized manually
+    #[account(mut, seeds = [LiquidationFee::SEED], bump)]
+    pub liquidation_fee: AccountInfo<'info>,
+
+    #[account(
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
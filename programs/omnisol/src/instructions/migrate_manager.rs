@@ -0,0 +1,54 @@
+use anchor_lang::{prelude::*, Discriminator};
+
+use crate::{
+    state::{GlobalConfig, LegacyManager, Manager},
+    utils,
+};
+
+/// The admin can migrate manager account created before managers got permissions.
+/// The account is reallocated, the admin pays the rent difference.
+/// Legacy managers could do everything, so they get all permissions,
+/// which can be restricted with `set_manager_permissions` afterwards.
+pub fn handle(ctx: Context<MigrateManager>) -> Result<()> {
+    let manager_info = ctx.accounts.manager.to_account_info();
+    let legacy_manager: LegacyManager =
+        utils::load_legacy(&manager_info, LegacyManager::SIZE, Manager::discriminator())?;
+
+    utils::realloc(
+        &manager_info,
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        Manager::SIZE,
+    )?;
+
+    let manager = Manager {
+        manager: legacy_manager.manager,
+        permissions: Manager::ALL_PERMISSIONS,
+    };
+    let mut data = manager_info.try_borrow_mut_data()?;
+    manager.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateManager<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    /// CHECK: Manager account of the previous layout, will be deserialized manually
+    #[account(mut, seeds = [Manager::SEED, manager_wallet.key().as_ref()], bump)]
+    pub manager: AccountInfo<'info>,
+
+    /// CHECK: Address of manager to migrate
+    pub manager_wallet: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
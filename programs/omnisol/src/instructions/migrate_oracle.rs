@@ -0,0 +1,51 @@
+use anchor_lang::{prelude::*, Discriminator};
+
+use crate::{
+    state::{GlobalConfig, LegacyOracle, Oracle},
+    utils,
+};
+
+/// The admin can migrate oracle account created before the queue members got users and rates.
+/// The account is reallocated to the initial size, the admin pays the rent difference,
+/// and grows to the full size on the next `update_oracle_info`.
+/// Legacy queue members can't be converted, so the queues are cleared until the oracle publishes them again.
+pub fn handle(ctx: Context<MigrateOracle>) -> Result<()> {
+    let oracle_info = ctx.accounts.oracle.to_account_info();
+    let legacy_oracle: LegacyOracle = utils::load_legacy(&oracle_info, LegacyOracle::SIZE, Oracle::discriminator())?;
+
+    utils::realloc(
+        &oracle_info,
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        Oracle::INIT_SIZE,
+    )?;
+
+    let oracle = Oracle {
+        authority: legacy_oracle.authority,
+        priority_queue: vec![],
+        staging_queue: vec![],
+    };
+    let mut data = oracle_info.try_borrow_mut_data()?;
+    oracle.try_serialize(&mut &mut data[..])?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateOracle<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    /// CHECK: Oracle account of the previous layout, will be deserialized manually
+    #[account(mut, seeds = [Oracle::SEED], bump)]
+    pub oracle: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
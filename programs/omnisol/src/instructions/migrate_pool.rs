@@ -0,0 +1,109 @@
+use anchor_lang::{prelude::*, system_program, Discriminator};
+
+use crate::{
+    state::{legacy_fee_to_bps, FeeMode, LegacyPool, Pool, Treasury, ValidatorPolicy},
+    utils, ErrorCode,
+};
+
+/// Anyone can migrate pool account created before the pool got its current layout.
+/// The account is reallocated, the fees are converted from 1/1000 to basis points and the new parameters get their defaults,
+/// the caller pays the rent difference and the rent of the pool treasury, that is initialized along with it.
+/// SOL fees collected on the pool authority before the treasury was introduced are moved to the treasury.
+pub fn handle(ctx: Context<MigratePool>) -> Result<()> {
+    let pool_info = ctx.accounts.pool.to_account_info();
+    let legacy_pool: LegacyPool = utils::load_legacy(&pool_info, LegacyPool::SIZE, Pool::discriminator())?;
+
+    utils::realloc(
+        &pool_info,
+        &ctx.accounts.authority.to_account_info(),
+        &ctx.accounts.system_program.to_account_info(),
+        Pool::SIZE,
+    )?;
+
+    let pool = Pool {
+        pool_mint: legacy_pool.pool_mint,
+        authority: legacy_pool.authority,
+        stake_source: legacy_pool.stake_source,
+        deposit_amount: legacy_pool.deposit_amount,
+        collaterals_amount: legacy_pool.collaterals_amount,
+        authority_bump: legacy_pool.authority_bump,
+        is_active: legacy_pool.is_active,
+        fee_receiver: legacy_pool.fee_receiver,
+        withdraw_fee: legacy_fee_to_bps(legacy_pool.withdraw_fee).ok_or(ErrorCode::TypeOverflow)?,
+        mint_fee: legacy_fee_to_bps(legacy_pool.mint_fee).ok_or(ErrorCode::TypeOverflow)?,
+        deposit_fee: legacy_fee_to_bps(legacy_pool.deposit_fee).ok_or(ErrorCode::TypeOverflow)?,
+        storage_fee: legacy_fee_to_bps(legacy_pool.storage_fee).ok_or(ErrorCode::TypeOverflow)?,
+        min_deposit: legacy_pool.min_deposit,
+        min_mint: 0,
+        min_burn: 0,
+        fee_mode: FeeMode::Sol,
+        fee_recipients: vec![],
+        referral_share: 0,
+        validator_policy: ValidatorPolicy::Any,
+    };
+    let mut data = pool_info.try_borrow_mut_data()?;
+    pool.try_serialize(&mut &mut data[..])?;
+
+    let treasury = &mut ctx.accounts.treasury;
+    treasury.pool = pool_info.key();
+    treasury.deposit_fees = 0;
+    treasury.mint_fees = 0;
+    treasury.withdraw_fees = 0;
+    treasury.storage_fees = 0;
+    treasury.liquidation_fees = 0;
+    treasury.swept_amount = 0;
+    treasury.bump = ctx.bumps["treasury"];
+    treasury.liquidation_fee_balance = 0;
+    treasury.in_kind_liquidation_fee_balance = 0;
+    treasury.escrowed_liquidation_fees = 0;
+
+    let pool_authority = ctx.accounts.pool_authority.to_account_info();
+    let rent_exempt_balance = Rent::get()?.minimum_balance(pool_authority.data_len());
+    let legacy_fees = pool_authority.lamports().saturating_sub(rent_exempt_balance);
+
+    if legacy_fees > 0 {
+        msg!("Move {} lamports of legacy fees to the treasury", legacy_fees);
+
+        let pool_key = pool_info.key();
+        let pool_authority_seeds = [pool_key.as_ref(), &[legacy_pool.authority_bump]];
+
+        system_program::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: pool_authority,
+                    to: ctx.accounts.treasury.to_account_info(),
+                },
+                &[&pool_authority_seeds],
+            ),
+            legacy_fees,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigratePool<'info> {
+    /// CHECK: Pool account of the previous layout, will be deserialized manually
+    #[account(mut)]
+    pub pool: AccountInfo<'info>,
+
+    /// CHECK: no needs to check, only for signing
+    #[account(mut, seeds = [pool.key().as_ref()], bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    #[account(
+        init,
+        seeds = [Treasury::SEED, pool.key().as_ref()],
+        bump,
+        payer = authority,
+        space = Treasury::SIZE,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
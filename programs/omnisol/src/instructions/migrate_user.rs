@@ -0,0 +1,151 @@
+use anchor_lang::{prelude::*, Discriminator};
+
+use crate::{
+    events::RegisterUserEvent,
+    instructions::migrate_legacy_collateral,
+    state::{Collateral, LegacyCollateral, LegacyUser, Oracle, Pool, QueueMember, User, Whitelist},
+    utils,
+    utils::stake_pool::{get_lamports_value, get_stake_pool},
+    ErrorCode,
+};
+
+/// The user can migrate their global user account to the per-pool one.
+/// Collaterals of the pool owned by the global account are passed as remaining accounts,
+/// they are moved to the per-pool user together with their part of the rate.
+/// LP token collaterals are valued in lamports, so each one is followed by its whitelist and stake pool accounts.
+/// Collaterals of the previous layout are reallocated on the way, the user pays the rent difference.
+/// Blocking is inherited from the global account.
+/// Caller provides [close_legacy] flag to close the global account once all collaterals
+/// and withdraw requests are migrated.
+pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, MigrateUser<'info>>, close_legacy: bool) -> Result<()> {
+    let pool_key = ctx.accounts.pool.key();
+    let legacy_user_info = ctx.accounts.legacy_user.to_account_info();
+
+    let mut legacy_user = if legacy_user_info.data_is_empty() {
+        None
+    } else {
+        Some(utils::load_legacy::<LegacyUser>(
+            &legacy_user_info,
+            LegacyUser::SIZE,
+            User::discriminator(),
+        )?)
+    };
+
+    let user = &mut ctx.accounts.user;
+
+    if user.wallet != ctx.accounts.authority.key() {
+        user.wallet = ctx.accounts.authority.key();
+        user.pool = pool_key;
+        user.rate = 0;
+        user.is_blocked = false;
+
+        emit!(RegisterUserEvent {
+            pool: pool_key,
+            user: user.key(),
+        });
+    }
+
+    if let Some(legacy_user) = &legacy_user {
+        user.is_blocked |= legacy_user.is_blocked;
+    }
+
+    let oracle = &mut ctx.accounts.oracle;
+
+    let mut remaining_accounts = ctx.remaining_accounts.iter();
+
+    while let Some(collateral_info) = remaining_accounts.next() {
+        if collateral_info.data_len() == LegacyCollateral::SIZE {
+            migrate_legacy_collateral(
+                collateral_info,
+                &ctx.accounts.authority.to_account_info(),
+                &ctx.accounts.system_program.to_account_info(),
+            )?;
+        }
+
+        let mut collateral = Account::<Collateral>::try_from(collateral_info)?;
+
+        if collateral.user != legacy_user_info.key() || collateral.pool != pool_key {
+            msg!("Collateral {} doesn't belong to the global user", collateral.key());
+            return Err(ErrorCode::WrongData.into());
+        }
+
+        let rest_value = if collateral.is_native {
+            collateral.get_rest_amount()
+        } else {
+            let whitelist_info = remaining_accounts.next().ok_or(ErrorCode::WrongData)?;
+            let stake_pool_info = remaining_accounts.next().ok_or(ErrorCode::WrongData)?;
+
+            let whitelist = Account::<Whitelist>::try_from(whitelist_info)?;
+            if whitelist.mint != collateral.stake_source {
+                return Err(ErrorCode::InvalidToken.into());
+            }
+
+            get_lamports_value(
+                &get_stake_pool(stake_pool_info, &whitelist)?,
+                collateral.get_rest_amount(),
+            )?
+        };
+
+        let rate = rest_value.saturating_sub(collateral.amount);
+        user.rate = user.rate.checked_add(rate).ok_or(ErrorCode::TypeOverflow)?;
+        if let Some(legacy_user) = &mut legacy_user {
+            legacy_user.rate = legacy_user.rate.saturating_sub(rate);
+        }
+
+        collateral.user = user.key();
+        collateral.exit(ctx.program_id)?;
+
+        oracle.upsert(QueueMember {
+            collateral: collateral.key(),
+            user: user.key(),
+            amount: rest_value,
+            rate: user.rate,
+        });
+    }
+
+    oracle.set_rate(user.key(), user.rate);
+
+    if let Some(legacy_user) = legacy_user {
+        if close_legacy {
+            if legacy_user.is_blocked {
+                return Err(ErrorCode::UserBlocked.into());
+            }
+            if legacy_user.requests_amount > 0 {
+                msg!("Migrate withdraw requests of the global user first");
+                return Err(ErrorCode::PendingWithdrawRequests.into());
+            }
+            utils::close(legacy_user_info, ctx.accounts.authority.to_account_info())?;
+        } else {
+            let mut data = legacy_user_info.try_borrow_mut_data()?;
+            legacy_user.serialize(&mut &mut data[8..])?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateUser<'info> {
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: Global user account, will be deserialized manually
+    #[account(mut, seeds = [User::SEED, authority.key().as_ref()], bump)]
+    pub legacy_user: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        seeds = [User::SEED, pool.key().as_ref(), authority.key().as_ref()],
+        bump,
+        payer = authority,
+        space = User::SIZE,
+    )]
+    pub user: Box<Account<'info, User>>,
+
+    #[account(mut, seeds = [Oracle::SEED], bump)]
+    pub oracle: Box<Account<'info, Oracle>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
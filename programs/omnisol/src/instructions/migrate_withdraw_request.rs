@@ -0,0 +1,121 @@
+use anchor_lang::{prelude::*, Discriminator};
+
+use crate::{
+    events::WithdrawRequestCreationEvent,
+    state::{FeeMode, LegacyUser, LegacyWithdrawInfo, Pool, User, WithdrawInfo, WithdrawMode},
+    utils, ErrorCode,
+};
+
+/// The user can migrate the first pending withdraw request of their global user account
+/// to the per-pool user account, that has to be migrated with `migrate_user` before.
+/// Requests are migrated in the order they were made and are appended to the per-pool requests,
+/// they are fulfilled in the instant mode without minimal lamports out.
+/// The liquidation fee of the legacy request was paid to the fee receiver, so it can't be refunded.
+pub fn handle(ctx: Context<MigrateWithdrawRequest>) -> Result<()> {
+    let legacy_user_info = ctx.accounts.legacy_user.to_account_info();
+    let mut legacy_user: LegacyUser = utils::load_legacy(&legacy_user_info, LegacyUser::SIZE, User::discriminator())?;
+
+    if legacy_user.requests_amount == 0 {
+        msg!("No withdraw requests to migrate");
+        return Err(ErrorCode::InsufficientAmount.into());
+    }
+
+    let authority_key = ctx.accounts.authority.key();
+    let legacy_withdraw_info_info = ctx.accounts.legacy_withdraw_info.to_account_info();
+    let (legacy_withdraw_info_key, _) = Pubkey::find_program_address(
+        &[
+            WithdrawInfo::SEED,
+            authority_key.as_ref(),
+            legacy_user.get_index().to_le_bytes().as_ref(),
+        ],
+        ctx.program_id,
+    );
+    if legacy_withdraw_info_key != legacy_withdraw_info_info.key() {
+        msg!("The first withdraw request of the global user is expected");
+        return Err(ErrorCode::WrongData.into());
+    }
+
+    let legacy_withdraw_info: LegacyWithdrawInfo = utils::load_legacy(
+        &legacy_withdraw_info_info,
+        LegacyWithdrawInfo::SIZE,
+        WithdrawInfo::discriminator(),
+    )?;
+
+    let pool_key = ctx.accounts.pool.key();
+    let withdraw_info = &mut ctx.accounts.withdraw_info;
+    withdraw_info.authority = legacy_withdraw_info.authority;
+    withdraw_info.pool = pool_key;
+    withdraw_info.amount = legacy_withdraw_info.amount;
+    withdraw_info.created_at = legacy_withdraw_info.created_at;
+    withdraw_info.mode = WithdrawMode::Instant;
+    withdraw_info.stake_account = Pubkey::default();
+    withdraw_info.claimable_epoch = 0;
+    withdraw_info.collateral_owner_wallet = Pubkey::default();
+    withdraw_info.min_lamports_out = 0;
+    withdraw_info.fee = 0;
+    withdraw_info.fee_mode = FeeMode::Sol;
+
+    let user = &mut ctx.accounts.user;
+    user.last_withdraw_index += 1;
+    user.requests_amount += 1;
+
+    legacy_user.requests_amount -= 1;
+    {
+        let mut data = legacy_user_info.try_borrow_mut_data()?;
+        legacy_user.serialize(&mut &mut data[8..])?;
+    }
+
+    utils::close(legacy_withdraw_info_info, ctx.accounts.authority.to_account_info())?;
+
+    emit!(WithdrawRequestCreationEvent {
+        pool: pool_key,
+        user: user.key(),
+        amount: withdraw_info.amount,
+        fee: 0,
+        fee_mode: ctx.accounts.pool.fee_mode,
+        mode: WithdrawMode::Instant,
+        min_lamports_out: 0,
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct MigrateWithdrawRequest<'info> {
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: Global user account, will be deserialized manually
+    #[account(mut, seeds = [User::SEED, authority.key().as_ref()], bump)]
+    pub legacy_user: AccountInfo<'info>,
+
+    /// CHECK: The first withdraw request of the global user, will be checked and deserialized manually
+    #[account(mut)]
+    pub legacy_withdraw_info: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [User::SEED, pool.key().as_ref(), authority.key().as_ref()],
+        bump,
+    )]
+    pub user: Box<Account<'info, User>>,
+
+    #[account(
+        init,
+        seeds = [
+            WithdrawInfo::SEED,
+            user.key().as_ref(),
+            user.next_index().to_le_bytes().as_ref()
+        ],
+        bump,
+        payer = authority,
+        space = WithdrawInfo::SIZE
+    )]
+    pub withdraw_info: Box<Account<'info, WithdrawInfo>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub system_program: Program<'info, System>,
+}
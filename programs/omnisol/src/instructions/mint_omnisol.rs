@@ -1,15 +1,26 @@
 use anchor_lang::{prelude::*, system_program};
-use anchor_spl::token;
+use anchor_spl::{associated_token::get_associated_token_address, token};
 
 use crate::{
     events::*,
-    state::{Collateral, Pool, User, MINT_AUTHORITY_SEED},
-    utils, ErrorCode,
+    state::{Collateral, FeeMode, FeeType, Oracle, Pool, QueueMember, Treasury, User, Whitelist, MINT_AUTHORITY_SEED},
+    utils,
+    utils::{
+        fee::{calc_fee, pay_referral_fee, record_fee},
+        stake,
+        stake_pool::{get_lamports_value, get_stake_pool},
+    },
+    ErrorCode,
 };
 
 /// The user can use their deposit to mint omniSOL.
 /// They can now withdraw this omniSOL and do whatever they want with it e.g. sell it, participate in DeFi, etc.
-pub fn handle(ctx: Context<MintOmnisol>, amount: u64) -> Result<()> {
+/// Native stake collateral can't be used for minting while its stake is deactivating.
+/// LP token collateral is valued in lamports by the current exchange rate of its stake pool,
+/// so the whitelist and the stake pool accounts have to be passed as remaining accounts.
+/// With in-kind fees the mint fee is withheld in omniSOL and minted to the fee receiver.
+/// The referral account and the referrer wallet can be passed next to share the SOL mint fee.
+pub fn handle<'info>(ctx: Context<'_, '_, '_, 'info, MintOmnisol<'info>>, amount: u64) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
     if !pool.is_active {
         return Err(ErrorCode::PoolAlreadyPaused.into());
@@ -20,33 +31,125 @@ pub fn handle(ctx: Context<MintOmnisol>, amount: u64) -> Result<()> {
         return Err(ErrorCode::UserBlocked.into());
     }
 
+    if amount < pool.min_mint {
+        msg!("Minimal mint amount is {} lamports", pool.min_mint);
+        return Err(ErrorCode::AmountBelowMinimum.into());
+    }
+
     let collateral = &mut ctx.accounts.collateral;
 
-    if amount > collateral.delegation_stake - collateral.amount {
+    // LP token collateral and its rest in the priority queue are valued in lamports
+    let (collateral_value, rest_value) = if collateral.is_native {
+        let delegated_stake = Account::<stake::StakeAccount>::try_from(&ctx.accounts.staked_address)?;
+        let delegation = delegated_stake.delegation().ok_or(ErrorCode::InvalidStakeAccount)?;
+
+        if delegation.deactivation_epoch != u64::MAX {
+            msg!("Stake is deactivating since epoch {}", delegation.deactivation_epoch);
+            return Err(ErrorCode::StakeNotActive.into());
+        }
+
+        (collateral.delegation_stake, collateral.get_rest_amount())
+    } else {
+        let whitelist_info = ctx.remaining_accounts.get(0).ok_or(ErrorCode::WrongData)?;
+        let stake_pool_info = ctx.remaining_accounts.get(1).ok_or(ErrorCode::WrongData)?;
+
+        let whitelist = Account::<Whitelist>::try_from(whitelist_info)?;
+        if whitelist.mint != collateral.stake_source {
+            return Err(ErrorCode::InvalidToken.into());
+        }
+
+        let stake_pool = get_stake_pool(stake_pool_info, &whitelist)?;
+        (
+            get_lamports_value(&stake_pool, collateral.delegation_stake)?,
+            get_lamports_value(&stake_pool, collateral.get_rest_amount())?,
+        )
+    };
+
+    if amount > collateral_value.saturating_sub(collateral.amount) {
         return Err(ErrorCode::InsufficientAmount.into());
     }
 
     let mint_authority_seeds = [MINT_AUTHORITY_SEED, &[ctx.bumps["mint_authority"]]];
     let clock = &ctx.accounts.clock;
 
-    if pool.mint_fee > 0 {
-        let fee = amount.saturating_div(1000).saturating_mul(pool.mint_fee as u64);
-        msg!("Transfer mint fee: {} lamports", fee);
-
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.fee_payer.to_account_info(),
-                    to: ctx.accounts.fee_receiver.to_account_info(),
-                },
-            ),
-            fee,
-        )
-        .map_err(|_| ErrorCode::InsufficientFunds)?;
+    let fee = calc_fee(amount, pool.mint_fee);
+    // part of the fee that is collected by the protocol
+    let mut treasury_fee = fee;
+
+    if fee > 0 {
+        match pool.fee_mode {
+            FeeMode::Sol => {
+                // referral accounts follow the LP token collateral accounts
+                let referral_accounts = ctx
+                    .remaining_accounts
+                    .get(if collateral.is_native { 0 } else { 2 }..)
+                    .unwrap_or_default();
+                let referral_fee = pay_referral_fee(
+                    referral_accounts,
+                    pool.key(),
+                    pool.referral_share,
+                    ctx.accounts.authority.key(),
+                    ctx.accounts.fee_payer.to_account_info(),
+                    ctx.accounts.system_program.to_account_info(),
+                    fee,
+                    clock.unix_timestamp,
+                )?;
+
+                msg!("Transfer mint fee: {} lamports", fee - referral_fee);
+
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.fee_payer.to_account_info(),
+                            to: ctx.accounts.treasury.to_account_info(),
+                        },
+                    ),
+                    fee - referral_fee,
+                )
+                .map_err(|_| ErrorCode::InsufficientFunds)?;
+
+                treasury_fee = fee - referral_fee;
+            }
+            FeeMode::InKind => {
+                if ctx.accounts.fee_receiver_pool_token.key()
+                    != get_associated_token_address(&pool.fee_receiver, &pool.pool_mint)
+                {
+                    return Err(ErrorCode::InvalidFeeReceiver.into());
+                }
+
+                msg!("Withhold mint fee: {} omni-lamports", fee);
+
+                token::mint_to(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        token::MintTo {
+                            mint: ctx.accounts.pool_mint.to_account_info(),
+                            to: ctx.accounts.fee_receiver_pool_token.to_account_info(),
+                            authority: ctx.accounts.mint_authority.to_account_info(),
+                        },
+                        &[&mint_authority_seeds],
+                    ),
+                    fee,
+                )?;
+            }
+        }
     }
 
-    // Mint new pool tokens equals to `amount`
+    record_fee(
+        &mut ctx.accounts.treasury,
+        FeeType::Mint,
+        pool.fee_mode,
+        treasury_fee,
+        clock.unix_timestamp,
+    );
+
+    let user_amount = match pool.fee_mode {
+        FeeMode::Sol => amount,
+        FeeMode::InKind => amount - fee,
+    };
+
+    // Mint new pool tokens equals to `amount` (without withheld fee)
     token::mint_to(
         CpiContext::new_with_signer(
             ctx.accounts.token_program.to_account_info(),
@@ -57,13 +160,23 @@ pub fn handle(ctx: Context<MintOmnisol>, amount: u64) -> Result<()> {
             },
             &[&mint_authority_seeds],
         ),
-        amount,
+        user_amount,
     )?;
 
     collateral.amount += amount;
-    user.rate -= amount;
+    // the rate is kept in lamports, so it can't underflow after the LP token appreciation
+    user.rate = user.rate.saturating_sub(amount);
 
-    if collateral.delegation_stake == collateral.liquidated_amount && collateral.amount == collateral.delegation_stake {
+    let oracle = &mut ctx.accounts.oracle;
+    oracle.set_rate(user.key(), user.rate);
+    oracle.upsert(QueueMember {
+        collateral: collateral.key(),
+        user: user.key(),
+        amount: rest_value,
+        rate: user.rate,
+    });
+
+    if collateral.is_closable(collateral_value) {
         // close the collateral account
         utils::close(collateral.to_account_info(), ctx.accounts.authority.to_account_info())?;
 
@@ -76,6 +189,8 @@ pub fn handle(ctx: Context<MintOmnisol>, amount: u64) -> Result<()> {
         collateral: collateral.key(),
         timestamp: clock.unix_timestamp,
         amount,
+        fee,
+        fee_mode: pool.fee_mode,
     });
 
     Ok(())
@@ -100,18 +215,21 @@ pub struct MintOmnisol<'info> {
 
     #[account(
         mut,
-        seeds = [User::SEED, authority.key().as_ref()],
+        seeds = [User::SEED, pool.key().as_ref(), authority.key().as_ref()],
         bump,
     )]
     pub user: Box<Account<'info, User>>,
 
     #[account(
         mut,
-        seeds = [Collateral::SEED, user.key().as_ref(), staked_address.key().as_ref()],
-        bump,
+        has_one = user,
+        constraint = collateral.get_source_stake() == staked_address.key(),
     )]
     pub collateral: Box<Account<'info, Collateral>>,
 
+    #[account(mut, seeds = [Oracle::SEED], bump)]
+    pub oracle: Box<Account<'info, Oracle>>,
+
     #[account(
         mut,
         associated_token::mint = pool_mint,
@@ -119,7 +237,7 @@ pub struct MintOmnisol<'info> {
     )]
     pub user_pool_token: Account<'info, token::TokenAccount>,
 
-    /// CHECK: only need for seeds
+    /// CHECK: Delegated stake account or LP token of the collateral
     pub staked_address: AccountInfo<'info>,
 
     #[account(mut)]
@@ -128,9 +246,12 @@ pub struct MintOmnisol<'info> {
     #[account(mut)]
     pub fee_payer: Signer<'info>,
 
-    /// CHECK: no needs to check, only for transfer
-    #[account(mut, address = pool.fee_receiver)]
-    pub fee_receiver: AccountInfo<'info>,
+    #[account(mut, seeds = [Treasury::SEED, pool.key().as_ref()], bump = treasury.bump)]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    /// CHECK: omniSOL token account of the fee receiver, checked only with in-kind fees
+    #[account(mut)]
+    pub fee_receiver_pool_token: AccountInfo<'info>,
 
     pub clock: Sysvar<'info, Clock>,
     pub token_program: Program<'info, token::Token>,
@@ -1,51 +1,101 @@
+pub mod accept_admin;
 pub mod add_liquidator;
 pub mod add_manager;
 pub mod add_to_token_whitelist;
+pub mod apply_pool_update;
 pub mod block_user;
 pub mod burn_omnisol;
+pub mod claim_withdrawal;
+pub mod cancel_pool_update;
+pub mod cancel_withdraw_request;
 pub mod close_oracle;
 pub mod close_pool;
+pub mod commit_oracle_info;
+pub mod deactivate_collateral;
+pub mod deactivate_for_withdrawal;
 pub mod deposit_lp_tokens;
 pub mod deposit_stake;
+pub mod init_global_config;
 pub mod init_oracle;
 pub mod init_pool;
 pub mod liquidate_collateral;
+pub mod migrate_collateral;
+pub mod migrate_liquidation_fee;
+pub mod migrate_manager;
+pub mod migrate_oracle;
+pub mod migrate_pool;
+pub mod migrate_user;
+pub mod migrate_withdraw_request;
 pub mod mint_omnisol;
 pub mod pause_pool;
+pub mod propose_admin;
+pub mod redelegate_collateral;
+pub mod register_referrer;
 pub mod remove_from_whitelist;
 pub mod remove_liquidator;
 pub mod remove_manager;
+pub mod remove_validator;
 pub mod resume_pool;
 pub mod set_liquidation_fee;
+pub mod set_manager_permissions;
+pub mod set_validator;
+pub mod settle_storage_fee;
+pub mod sweep_liquidation_fees;
+pub mod sweep_treasury;
+pub mod sync_collateral;
 pub mod unblock_user;
 pub mod update_oracle_info;
 pub mod update_pool;
 pub mod withdraw_lp_tokens;
-pub mod withdraw_sol;
 pub mod withdraw_stake;
 
+pub use accept_admin::*;
 pub use add_liquidator::*;
 pub use add_manager::*;
 pub use add_to_token_whitelist::*;
+pub use apply_pool_update::*;
 pub use block_user::*;
 pub use burn_omnisol::*;
+pub use claim_withdrawal::*;
+pub use cancel_pool_update::*;
+pub use cancel_withdraw_request::*;
 pub use close_oracle::*;
 pub use close_pool::*;
+pub use commit_oracle_info::*;
+pub use deactivate_collateral::*;
+pub use deactivate_for_withdrawal::*;
 pub use deposit_lp_tokens::*;
 pub use deposit_stake::*;
+pub use init_global_config::*;
 pub use init_oracle::*;
 pub use init_pool::*;
 pub use liquidate_collateral::*;
+pub use migrate_collateral::*;
+pub use migrate_liquidation_fee::*;
+pub use migrate_manager::*;
+pub use migrate_oracle::*;
+pub use migrate_pool::*;
+pub use migrate_user::*;
+pub use migrate_withdraw_request::*;
 pub use mint_omnisol::*;
 pub use pause_pool::*;
+pub use propose_admin::*;
+pub use redelegate_collateral::*;
+pub use register_referrer::*;
 pub use remove_from_whitelist::*;
 pub use remove_liquidator::*;
 pub use remove_manager::*;
+pub use remove_validator::*;
 pub use resume_pool::*;
 pub use set_liquidation_fee::*;
+pub use set_manager_permissions::*;
+pub use set_validator::*;
+pub use settle_storage_fee::*;
+pub use sweep_liquidation_fees::*;
+pub use sweep_treasury::*;
+pub use sync_collateral::*;
 pub use unblock_user::*;
 pub use update_oracle_info::*;
 pub use update_pool::*;
 pub use withdraw_lp_tokens::*;
-pub use withdraw_sol::*;
 pub use withdraw_stake::*;
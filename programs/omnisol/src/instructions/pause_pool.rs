@@ -24,7 +24,12 @@ pub struct PausePool<'info> {
     #[account(mut)]
     pub pool: Box<Account<'info, Pool>>,
 
-    #[account(mut, seeds = [Manager::SEED, authority.key().as_ref()], bump)]
+    #[account(
+        mut,
+        seeds = [Manager::SEED, authority.key().as_ref()],
+        bump,
+        constraint = manager.has_permission(Manager::PAUSE)
+    )]
     pub manager: Box<Account<'info, Manager>>,
 
     #[account(mut)]
@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+
+use crate::state::GlobalConfig;
+
+/// The admin can propose new admin.
+/// The role is transferred only after the new admin accepts it.
+pub fn handle(ctx: Context<ProposeAdmin>) -> Result<()> {
+    let global_config = &mut ctx.accounts.global_config;
+
+    global_config.pending_admin = Some(ctx.accounts.new_admin.key());
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    /// CHECK: Address of the proposed admin
+    pub new_admin: AccountInfo<'info>,
+}
@@ -0,0 +1,115 @@
+use anchor_lang::{prelude::*, solana_program::stake::config as stake_config};
+
+use crate::{
+    events::*,
+    state::{Collateral, Pool, Validator},
+    utils::{stake, validator::load_validator},
+    ErrorCode,
+};
+
+/// Anyone can redelegate the deactivated stake of the collateral to the validator chosen by the manager.
+/// The collateral amount is removed from the previous validator entry, the new one has reserved it on deactivation.
+/// The whole stake is delegated, so the lamports above the collateral amount are recorded
+/// as the redelegation excess, that isn't synced as rewards.
+pub fn handle(ctx: Context<RedelegateCollateral>) -> Result<()> {
+    let pool = &ctx.accounts.pool;
+    let collateral = &mut ctx.accounts.collateral;
+    let clock = &ctx.accounts.clock;
+
+    let delegation = ctx
+        .accounts
+        .delegated_stake
+        .delegation()
+        .ok_or(ErrorCode::InvalidStakeAccount)?;
+
+    if !stake::is_inactive(&delegation, clock.epoch, &ctx.accounts.stake_history) {
+        msg!("Stake is deactivating since epoch {}", delegation.deactivation_epoch);
+        return Err(ErrorCode::StakeNotDeactivated.into());
+    }
+
+    let pool_key = pool.key();
+    let pool_authority_seeds = [pool_key.as_ref(), &[pool.authority_bump]];
+
+    stake::delegate(CpiContext::new_with_signer(
+        ctx.accounts.stake_program.to_account_info(),
+        stake::Delegate {
+            stake: ctx.accounts.delegated_stake.to_account_info(),
+            vote_account: ctx.accounts.vote_account.to_account_info(),
+            authority: ctx.accounts.pool_authority.to_account_info(),
+            clock: clock.to_account_info(),
+            stake_history: ctx.accounts.stake_history.to_account_info(),
+            stake_config: ctx.accounts.stake_config.to_account_info(),
+        },
+        &[&pool_authority_seeds],
+    ))?;
+
+    let amount = collateral.get_rest_amount();
+
+    ctx.accounts.delegated_stake.reload()?;
+    let delegated_amount = ctx
+        .accounts
+        .delegated_stake
+        .delegation()
+        .ok_or(ErrorCode::InvalidStakeAccount)?
+        .stake;
+
+    collateral.redelegation_excess = delegated_amount.saturating_sub(amount);
+
+    if let Some(mut validator) = load_validator(&ctx.accounts.previous_validator)? {
+        validator.collateral_amount = validator.collateral_amount.saturating_sub(amount);
+        validator.exit(&crate::ID)?;
+    }
+
+    collateral.pending_vote_account = None;
+
+    emit!(RedelegateCollateralEvent {
+        pool: pool_key,
+        collateral: collateral.key(),
+        previous_vote_account: delegation.voter_pubkey,
+        vote_account: ctx.accounts.vote_account.key(),
+        amount: delegated_amount,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RedelegateCollateral<'info> {
+    #[account(address = collateral.pool)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: no needs to check, only for signing
+    #[account(seeds = [pool.key().as_ref()], bump = pool.authority_bump)]
+    pub pool_authority: AccountInfo<'info>,
+
+    #[account(mut)]
+    pub collateral: Box<Account<'info, Collateral>>,
+
+    #[account(mut, constraint = collateral.delegated_stake == delegated_stake.key())]
+    pub delegated_stake: Box<Account<'info, stake::StakeAccount>>,
+
+    /// CHECK: Vote account of the validator chosen by the manager
+    #[account(constraint = collateral.pending_vote_account == Some(vote_account.key()))]
+    pub vote_account: AccountInfo<'info>,
+
+    /// CHECK: validator entry of the previous vote account, may be uninitialized
+    #[account(
+        mut,
+        seeds = [
+            Validator::SEED,
+            pool.key().as_ref(),
+            delegated_stake.delegation().map(|d| d.voter_pubkey).unwrap_or_default().as_ref(),
+        ],
+        bump,
+    )]
+    pub previous_validator: AccountInfo<'info>,
+
+    /// CHECK: Stake config account
+    #[account(address = stake_config::ID)]
+    pub stake_config: AccountInfo<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub stake_history: Sysvar<'info, StakeHistory>,
+    pub stake_program: Program<'info, stake::Stake>,
+}
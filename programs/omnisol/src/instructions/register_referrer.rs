@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::state::{Pool, Referral};
+
+/// Anyone can register as a referrer of the pool.
+/// The referrer gets the pool's referral share of deposit and mint fees paid by referred users.
+pub fn handle(ctx: Context<RegisterReferrer>) -> Result<()> {
+    let referral = &mut ctx.accounts.referral;
+
+    referral.referrer = ctx.accounts.authority.key();
+    referral.pool = ctx.accounts.pool.key();
+    referral.total_fees = 0;
+    referral.bump = ctx.bumps["referral"];
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RegisterReferrer<'info> {
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(
+        init,
+        seeds = [Referral::SEED, pool.key().as_ref(), authority.key().as_ref()],
+        bump,
+        payer = authority,
+        space = Referral::SIZE,
+    )]
+    pub referral: Box<Account<'info, Referral>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -30,6 +30,7 @@ pub struct RemoveFromWhitelist<'info> {
             authority.key().as_ref(),
         ],
         bump,
+        constraint = manager.has_permission(Manager::WHITELIST)
     )]
     pub manager: Box<Account<'info, Manager>>,
 
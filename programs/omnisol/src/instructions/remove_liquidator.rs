@@ -1,8 +1,8 @@
 use anchor_lang::prelude::*;
 
-use crate::state::{Liquidator, Manager};
+use crate::state::{GlobalConfig, Liquidator};
 
-/// The manager can remove liquidator.
+/// The admin can remove liquidator.
 pub fn handle(_ctx: Context<RemoveLiquidator>) -> Result<()> {
     Ok(())
 }
@@ -27,14 +27,11 @@ pub struct RemoveLiquidator<'info> {
     pub liquidator: Box<Account<'info, Liquidator>>,
 
     #[account(
-        mut,
-        seeds = [
-            Manager::SEED,
-            authority.key().as_ref(),
-        ],
+        seeds = [GlobalConfig::SEED],
         bump,
+        constraint = global_config.admin == authority.key()
     )]
-    pub manager: Box<Account<'info, Manager>>,
+    pub global_config: Box<Account<'info, GlobalConfig>>,
 
     pub system_program: Program<'info, System>,
 }
@@ -1,8 +1,6 @@
-use std::str::FromStr;
-
 use anchor_lang::prelude::*;
 
-use crate::state::{Manager, ADMIN};
+use crate::state::{GlobalConfig, Manager};
 
 /// The admin can remove manager.
 pub fn handle(_ctx: Context<RemoveManager>) -> Result<()> {
@@ -11,9 +9,16 @@ pub fn handle(_ctx: Context<RemoveManager>) -> Result<()> {
 
 #[derive(Accounts)]
 pub struct RemoveManager<'info> {
-    #[account(mut, constraint = authority.key() == Pubkey::from_str(ADMIN).unwrap())]
+    #[account(mut)]
     pub authority: Signer<'info>,
 
+    #[account(
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
     #[account(
         mut,
         seeds = [
@@ -0,0 +1,35 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    state::{Manager, Validator},
+    ErrorCode,
+};
+
+/// The manager can remove the validator entry of the pool, once no collateral is deposited with the validator.
+/// Otherwise the entry can only be updated with `set_validator`, so its collateral amount keeps counting to the cap.
+pub fn handle(ctx: Context<RemoveValidator>) -> Result<()> {
+    let validator = &ctx.accounts.validator;
+
+    if validator.collateral_amount > 0 {
+        msg!("Validator has {} lamports of collateral", validator.collateral_amount);
+        return Err(ErrorCode::ValidatorHasCollateral.into());
+    }
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct RemoveValidator<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(mut, close = authority)]
+    pub validator: Box<Account<'info, Validator>>,
+
+    #[account(
+        seeds = [Manager::SEED, authority.key().as_ref()],
+        bump,
+        constraint = manager.has_permission(Manager::WHITELIST)
+    )]
+    pub manager: Box<Account<'info, Manager>>,
+}
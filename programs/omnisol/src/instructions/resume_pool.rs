@@ -26,6 +26,7 @@ pub struct ResumePool<'info> {
     #[account(mut,
         seeds = [Manager::SEED, authority.key().as_ref()],
         bump,
+        constraint = manager.has_permission(Manager::PAUSE)
     )]
     pub manager: Box<Account<'info, Manager>>,
 
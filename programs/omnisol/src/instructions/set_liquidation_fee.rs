@@ -1,12 +1,17 @@
 use anchor_lang::prelude::*;
 
 use crate::{
-    state::{LiquidationFee, Manager},
-    ErrorCode,
+    state::{FeeRecipient, GlobalConfig, LiquidationFee},
+    utils::fee::{check_fee, check_fee_recipients},
 };
 
-/// The manager can set liquidation fee.
-pub fn handle(ctx: Context<SetLiquidationFee>, fee: Option<u16>, fee_receiver: Option<Pubkey>) -> Result<()> {
+/// The admin can set liquidation fee, its receiver and recipients of the fee shares.
+pub fn handle(
+    ctx: Context<SetLiquidationFee>,
+    fee: Option<u16>,
+    fee_receiver: Option<Pubkey>,
+    fee_recipients: Option<Vec<FeeRecipient>>,
+) -> Result<()> {
     let liquidation_fee = &mut ctx.accounts.liquidation_fee;
 
     if let Some(fee_receiver) = fee_receiver {
@@ -14,13 +19,15 @@ pub fn handle(ctx: Context<SetLiquidationFee>, fee: Option<u16>, fee_receiver: O
     }
 
     if let Some(fee) = fee {
-        if fee > 1000 {
-            msg!("Invalid liquidation fee value");
-            return Err(ErrorCode::WrongData.into());
-        }
+        check_fee(fee)?;
         liquidation_fee.fee = fee;
     }
 
+    if let Some(fee_recipients) = fee_recipients {
+        check_fee_recipients(&fee_recipients)?;
+        liquidation_fee.fee_recipients = fee_recipients;
+    }
+
     Ok(())
 }
 
@@ -35,8 +42,12 @@ pub struct SetLiquidationFee<'info> {
     )]
     pub liquidation_fee: Box<Account<'info, LiquidationFee>>,
 
-    #[account(mut, seeds = [Manager::SEED, authority.key().as_ref()], bump)]
-    pub manager: Box<Account<'info, Manager>>,
+    #[account(
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
 
     #[account(mut)]
     pub authority: Signer<'info>,
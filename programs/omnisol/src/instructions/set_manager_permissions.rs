@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    state::{GlobalConfig, Manager},
+    ErrorCode,
+};
+
+/// The admin can change permissions of the manager.
+pub fn handle(ctx: Context<SetManagerPermissions>, permissions: u8) -> Result<()> {
+    let manager = &mut ctx.accounts.manager;
+
+    if !Manager::is_valid_permissions(permissions) {
+        msg!("Invalid permissions");
+        return Err(ErrorCode::WrongData.into());
+    }
+
+    manager.permissions = permissions;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetManagerPermissions<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    #[account(
+        mut,
+        seeds = [
+            Manager::SEED,
+            manager_wallet.key().as_ref(),
+        ],
+        bump,
+    )]
+    pub manager: Box<Account<'info, Manager>>,
+
+    /// CHECK: Address of manager to update
+    pub manager_wallet: AccountInfo<'info>,
+}
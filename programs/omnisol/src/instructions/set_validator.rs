@@ -0,0 +1,52 @@
+use anchor_lang::{prelude::*, solana_program::vote};
+
+use crate::state::{Manager, Pool, Validator};
+
+/// The manager can add the validator to the pool's allowlist or denylist, or update its entry.
+/// Caller provides [max_collateral] that limits the amount of lamports deposited with the validator,
+/// zero means unlimited.
+pub fn handle(ctx: Context<SetValidator>, is_allowed: bool, max_collateral: u64) -> Result<()> {
+    let validator = &mut ctx.accounts.validator;
+
+    if validator.pool != ctx.accounts.pool.key() {
+        validator.pool = ctx.accounts.pool.key();
+        validator.vote_account = ctx.accounts.vote_account.key();
+        validator.collateral_amount = 0;
+        validator.bump = ctx.bumps["validator"];
+    }
+
+    validator.is_allowed = is_allowed;
+    validator.max_collateral = max_collateral;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SetValidator<'info> {
+    pub pool: Box<Account<'info, Pool>>,
+
+    /// CHECK: Vote account of the validator
+    #[account(owner = vote::program::ID)]
+    pub vote_account: AccountInfo<'info>,
+
+    #[account(
+        init_if_needed,
+        seeds = [Validator::SEED, pool.key().as_ref(), vote_account.key().as_ref()],
+        bump,
+        payer = authority,
+        space = Validator::SIZE,
+    )]
+    pub validator: Box<Account<'info, Validator>>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [Manager::SEED, authority.key().as_ref()],
+        bump,
+        constraint = manager.has_permission(Manager::WHITELIST)
+    )]
+    pub manager: Box<Account<'info, Manager>>,
+
+    pub system_program: Program<'info, System>,
+}
@@ -0,0 +1,39 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    events::*,
+    state::{Collateral, Pool},
+};
+
+/// Anyone can settle the storage fee of the collateral.
+/// The fee for the epochs since the last settlement is accrued on the collateral
+/// and is paid by the user on withdrawal.
+pub fn handle(ctx: Context<SettleStorageFee>) -> Result<()> {
+    let pool = &ctx.accounts.pool;
+    let collateral = &mut ctx.accounts.collateral;
+    let clock = &ctx.accounts.clock;
+
+    let fee = collateral.settle_storage_fee(pool.storage_fee, clock.epoch);
+
+    emit!(SettleStorageFeeEvent {
+        pool: pool.key(),
+        collateral: collateral.key(),
+        fee,
+        accrued_storage_fee: collateral.accrued_storage_fee,
+        fee_paid_through_epoch: collateral.fee_paid_through_epoch,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SettleStorageFee<'info> {
+    #[account(address = collateral.pool)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(mut)]
+    pub collateral: Box<Account<'info, Collateral>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
@@ -0,0 +1,121 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::get_associated_token_address, token};
+
+use crate::{
+    events::SweepTreasuryEvent,
+    state::{GlobalConfig, LiquidationFee, Pool, Treasury, MINT_AUTHORITY_SEED},
+    utils::{fee::pay_fee_recipients, transfer_lamports},
+    ErrorCode,
+};
+
+/// The admin can sweep liquidation fees of fulfilled withdraw requests from the pool treasury
+/// to the liquidation fee receiver. In-kind fees are minted as omniSOL to the fee receiver.
+/// Liquidation fee recipients get their shares of the SOL fees and have to be passed as remaining accounts in the same order.
+/// Shares too small for the rent-exempt minimum of a new recipient wallet go to the fee receiver.
+pub fn handle(ctx: Context<SweepLiquidationFees>) -> Result<()> {
+    let amount = ctx.accounts.treasury.liquidation_fee_balance;
+    let in_kind_amount = ctx.accounts.treasury.in_kind_liquidation_fee_balance;
+
+    if amount == 0 && in_kind_amount == 0 {
+        msg!("No liquidation fees to sweep");
+        return Err(ErrorCode::InsufficientAmount.into());
+    }
+
+    if in_kind_amount > 0 {
+        if ctx.accounts.fee_receiver_pool_token.key()
+            != get_associated_token_address(&ctx.accounts.liquidation_fee.fee_receiver, &ctx.accounts.pool.pool_mint)
+        {
+            return Err(ErrorCode::InvalidFeeReceiver.into());
+        }
+
+        let mint_authority_seeds = [MINT_AUTHORITY_SEED, &[ctx.bumps["mint_authority"]]];
+
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::MintTo {
+                    mint: ctx.accounts.pool_mint.to_account_info(),
+                    to: ctx.accounts.fee_receiver_pool_token.to_account_info(),
+                    authority: ctx.accounts.mint_authority.to_account_info(),
+                },
+                &[&mint_authority_seeds],
+            ),
+            in_kind_amount,
+        )?;
+
+        ctx.accounts.treasury.in_kind_liquidation_fee_balance = 0;
+    }
+
+    if amount == 0 {
+        return Ok(());
+    }
+
+    let treasury = ctx.accounts.treasury.to_account_info();
+    let rest_amount = pay_fee_recipients(
+        &treasury,
+        &ctx.accounts.liquidation_fee.fee_recipients,
+        ctx.remaining_accounts,
+        amount,
+    )?;
+
+    let destination = ctx.accounts.fee_receiver.to_account_info();
+    transfer_lamports(&treasury, &destination, rest_amount)?;
+
+    let treasury = &mut ctx.accounts.treasury;
+    treasury.liquidation_fee_balance = 0;
+    treasury.swept_amount = treasury.swept_amount.saturating_add(amount);
+
+    emit!(SweepTreasuryEvent {
+        pool: ctx.accounts.pool.key(),
+        authority: ctx.accounts.authority.key(),
+        destination: destination.key(),
+        amount,
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SweepLiquidationFees<'info> {
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(
+        mut,
+        seeds = [Treasury::SEED, pool.key().as_ref()],
+        bump = treasury.bump,
+        has_one = pool,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    #[account(seeds = [LiquidationFee::SEED], bump)]
+    pub liquidation_fee: Box<Account<'info, LiquidationFee>>,
+
+    /// CHECK: wallet for transfer
+    #[account(mut, address = liquidation_fee.fee_receiver)]
+    pub fee_receiver: AccountInfo<'info>,
+
+    /// CHECK: token program will check it
+    #[account(mut, address = pool.pool_mint)]
+    pub pool_mint: AccountInfo<'info>,
+
+    /// CHECK: no needs to check, only for signing
+    #[account(seeds = [MINT_AUTHORITY_SEED], bump)]
+    pub mint_authority: AccountInfo<'info>,
+
+    /// CHECK: omniSOL token account of the fee receiver, checked only with in-kind fees
+    #[account(mut)]
+    pub fee_receiver_pool_token: AccountInfo<'info>,
+
+    #[account(
+        seeds = [GlobalConfig::SEED],
+        bump,
+        constraint = global_config.admin == authority.key()
+    )]
+    pub global_config: Box<Account<'info, GlobalConfig>>,
+
+    pub authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub token_program: Program<'info, token::Token>,
+}
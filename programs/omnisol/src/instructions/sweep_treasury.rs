@@ -0,0 +1,80 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    events::SweepTreasuryEvent,
+    state::{Manager, Pool, Treasury},
+    utils::{fee::pay_fee_recipients, transfer_lamports},
+    ErrorCode,
+};
+
+/// The manager can sweep collected SOL fees from the pool treasury to the pool fee receiver.
+/// Caller provides some [amount] of lamports, the treasury always keeps its rent-exempt balance
+/// and the liquidation fees, that are escrowed or swept to the liquidation fee receivers with `sweep_liquidation_fees`.
+/// Pool fee recipients get their shares of the amount and have to be passed as remaining accounts in the same order.
+/// Shares too small for the rent-exempt minimum of a new recipient wallet go to the fee receiver.
+pub fn handle(ctx: Context<SweepTreasury>, amount: u64) -> Result<()> {
+    let treasury = ctx.accounts.treasury.to_account_info();
+    let rent_exempt_balance = ctx.accounts.rent.minimum_balance(treasury.data_len());
+    let available_amount = treasury
+        .lamports()
+        .saturating_sub(rent_exempt_balance)
+        .saturating_sub(ctx.accounts.treasury.liquidation_fee_balance)
+        .saturating_sub(ctx.accounts.treasury.escrowed_liquidation_fees);
+
+    if amount == 0 || amount > available_amount {
+        msg!("Available amount is {} lamports", available_amount);
+        return Err(ErrorCode::InsufficientAmount.into());
+    }
+
+    let rest_amount = pay_fee_recipients(
+        &treasury,
+        &ctx.accounts.pool.fee_recipients,
+        ctx.remaining_accounts,
+        amount,
+    )?;
+
+    let destination = ctx.accounts.fee_receiver.to_account_info();
+    transfer_lamports(&treasury, &destination, rest_amount)?;
+
+    let treasury = &mut ctx.accounts.treasury;
+    treasury.swept_amount = treasury.swept_amount.saturating_add(amount);
+
+    emit!(SweepTreasuryEvent {
+        pool: ctx.accounts.pool.key(),
+        authority: ctx.accounts.authority.key(),
+        destination: destination.key(),
+        amount,
+        timestamp: ctx.accounts.clock.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SweepTreasury<'info> {
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(
+        mut,
+        seeds = [Treasury::SEED, pool.key().as_ref()],
+        bump = treasury.bump,
+        has_one = pool,
+    )]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    /// CHECK: wallet for transfer
+    #[account(mut, address = pool.fee_receiver)]
+    pub fee_receiver: AccountInfo<'info>,
+
+    #[account(
+        seeds = [Manager::SEED, authority.key().as_ref()],
+        bump,
+        constraint = manager.has_permission(Manager::TREASURY)
+    )]
+    pub manager: Box<Account<'info, Manager>>,
+
+    pub authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub rent: Sysvar<'info, Rent>,
+}
@@ -0,0 +1,84 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    state::{Collateral, Oracle, Pool, User, Validator},
+    utils::{collateral::sync_rewards, stake},
+    ErrorCode,
+};
+
+/// Anyone can sync native stake collateral with its delegated stake account.
+/// Staking rewards accumulated by the stake account are added to the collateral,
+/// so the user can mint omniSOL on earned yield.
+pub fn handle(ctx: Context<SyncCollateral>) -> Result<()> {
+    let collateral = &mut ctx.accounts.collateral;
+
+    if !collateral.is_native {
+        return Err(ErrorCode::InvalidStakeAccount.into());
+    }
+
+    let delegated_stake = &ctx.accounts.delegated_stake;
+    let meta = delegated_stake.meta().ok_or(ErrorCode::InvalidStakeAccount)?;
+    let delegation = delegated_stake.delegation().ok_or(ErrorCode::InvalidStakeAccount)?;
+
+    let pool_key = ctx.accounts.pool.key();
+    let pool_authority = Pubkey::create_program_address(
+        &[pool_key.as_ref(), &[ctx.accounts.pool.authority_bump]],
+        ctx.program_id,
+    )
+    .map_err(|_| ErrorCode::WrongData)?;
+
+    // the stake left the pool, is leaving it or is being moved, so there is nothing to credit
+    if meta.authorized.staker != pool_authority
+        || meta.authorized.withdrawer != pool_authority
+        || delegation.deactivation_epoch != u64::MAX
+        || collateral.pending_vote_account.is_some()
+        || collateral.get_rest_amount() == 0
+    {
+        msg!("Collateral can't be synced");
+        return Ok(());
+    }
+
+    sync_rewards(
+        &mut ctx.accounts.pool,
+        &mut ctx.accounts.user,
+        collateral,
+        &mut ctx.accounts.oracle,
+        &ctx.accounts.validator,
+        delegation.stake,
+        &ctx.accounts.clock,
+    )?;
+
+    Ok(())
+}
+
+#[derive(Accounts)]
+pub struct SyncCollateral<'info> {
+    #[account(mut, address = collateral.pool)]
+    pub pool: Box<Account<'info, Pool>>,
+
+    #[account(mut, address = collateral.user)]
+    pub user: Box<Account<'info, User>>,
+
+    #[account(mut)]
+    pub collateral: Box<Account<'info, Collateral>>,
+
+    #[account(constraint = collateral.delegated_stake == delegated_stake.key())]
+    pub delegated_stake: Box<Account<'info, stake::StakeAccount>>,
+
+    /// CHECK: validator entry of the stake's vote account, may be uninitialized
+    #[account(
+        mut,
+        seeds = [
+            Validator::SEED,
+            pool.key().as_ref(),
+            delegated_stake.delegation().map(|d| d.voter_pubkey).unwrap_or_default().as_ref(),
+        ],
+        bump,
+    )]
+    pub validator: AccountInfo<'info>,
+
+    #[account(mut, seeds = [Oracle::SEED], bump)]
+    pub oracle: Box<Account<'info, Oracle>>,
+
+    pub clock: Sysvar<'info, Clock>,
+}
@@ -1,11 +1,11 @@
 use anchor_lang::prelude::*;
 
 use crate::{
-    state::{Manager, User},
+    state::{Manager, Pool, User},
     ErrorCode,
 };
 
-/// The manager can unblock user.
+/// The manager can unblock user in the pool.
 pub fn handle(ctx: Context<UnblockUser>) -> Result<()> {
     let user = &mut ctx.accounts.user;
 
@@ -27,16 +27,20 @@ pub struct UnblockUser<'info> {
             authority.key().as_ref(),
         ],
         bump,
+        constraint = manager.has_permission(Manager::BLOCK)
     )]
     pub manager: Box<Account<'info, Manager>>,
 
     #[account(mut)]
     pub authority: Signer<'info>,
 
+    pub pool: Box<Account<'info, Pool>>,
+
     #[account(
         mut,
         seeds = [
             User::SEED,
+            pool.key().as_ref(),
             user_wallet.key().as_ref(),
         ],
         bump
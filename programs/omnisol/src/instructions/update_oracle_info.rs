@@ -6,21 +6,22 @@ use crate::{
 };
 
 /// The oracle can update priority queue data.
-/// It does it by the special algorithm off-chain and save update by this instruction.
-pub fn handle(ctx: Context<UpdateOracleInfo>, addresses: Vec<Pubkey>, values: Vec<u64>, clear: bool) -> Result<()> {
+/// The queue is maintained on-chain by user instructions, so the oracle only reconciles it with off-chain data.
+/// Members are written to the staging queue in batches and become active after `commit_oracle_info`.
+/// The oracle account is reallocated to fit both full queues, if it isn't yet.
+pub fn handle(ctx: Context<UpdateOracleInfo>, members: Vec<QueueMember>, clear: bool) -> Result<()> {
     let oracle = &mut ctx.accounts.oracle;
 
-    if addresses.is_empty() || values.is_empty() || addresses.len() != values.len() {
+    if members.len() > Oracle::MAX_BATCH_LENGTH || members.is_empty() && !clear {
         return Err(ErrorCode::WrongData.into());
     }
 
     if clear {
-        oracle.priority_queue = vec![];
+        oracle.staging_queue = vec![];
     }
 
-    for (collateral, amount) in addresses.into_iter().zip(values.into_iter()) {
-        let queue_member = QueueMember { collateral, amount };
-        oracle.priority_queue.push(queue_member);
+    for queue_member in members {
+        oracle.stage(queue_member);
     }
 
     Ok(())
@@ -35,7 +36,10 @@ pub struct UpdateOracleInfo<'info> {
         mut,
         seeds = [Oracle::SEED],
         bump,
-        has_one = authority
+        has_one = authority,
+        realloc = Oracle::SIZE,
+        realloc::payer = authority,
+        realloc::zero = false,
     )]
     pub oracle: Box<Account<'info, Oracle>>,
 
@@ -1,64 +1,77 @@
 use anchor_lang::prelude::*;
 
 use crate::{
-    state::{Manager, Pool},
-    utils::fee::check_fee,
+    events::PoolUpdateProposedEvent,
+    state::{Manager, PendingPoolUpdate, Pool, UpdatePoolData},
+    utils::fee::{check_fee, check_fee_recipients},
 };
 
-/// The manager can update pool.
-/// All possible pool fees, fee receiver wallet and minimal pool deposit value can be updated.
+/// The manager can propose pool update.
+/// All possible pool fees, fee receiver and recipient wallets, referral share
+/// and minimal pool deposit, mint and burn values can be updated.
+/// The update is timelocked and can be applied by `apply_pool_update` after the delay.
 pub fn handle(ctx: Context<UpdatePool>, data: UpdatePoolData) -> Result<()> {
-    let pool = &mut ctx.accounts.pool;
+    let pending_pool_update = &mut ctx.accounts.pending_pool_update;
+    let clock = &ctx.accounts.clock;
 
-    if let Some(fee_receiver) = data.fee_receiver {
-        pool.fee_receiver = fee_receiver;
+    for fee in [
+        data.withdraw_fee,
+        data.deposit_fee,
+        data.mint_fee,
+        data.storage_fee,
+        data.referral_share,
+    ]
+    .into_iter()
+    .flatten()
+    {
+        check_fee(fee)?;
     }
 
-    if let Some(withdraw_fee) = data.withdraw_fee {
-        check_fee(withdraw_fee)?;
-        pool.withdraw_fee = withdraw_fee;
+    if let Some(fee_recipients) = &data.fee_recipients {
+        check_fee_recipients(fee_recipients)?;
     }
 
-    if let Some(deposit_fee) = data.deposit_fee {
-        check_fee(deposit_fee)?;
-        pool.deposit_fee = deposit_fee;
-    }
-
-    if let Some(mint_fee) = data.mint_fee {
-        check_fee(mint_fee)?;
-        pool.mint_fee = mint_fee;
-    }
+    pending_pool_update.pool = ctx.accounts.pool.key();
+    pending_pool_update.authority = ctx.accounts.authority.key();
+    pending_pool_update.data = data;
+    pending_pool_update.activation_epoch = clock.epoch + PendingPoolUpdate::DELAY_EPOCHS;
+    pending_pool_update.created_at = clock.unix_timestamp;
 
-    if let Some(storage_fee) = data.storage_fee {
-        check_fee(storage_fee)?;
-        pool.storage_fee = storage_fee;
-    }
-
-    if let Some(min_deposit) = data.min_deposit {
-        pool.min_deposit = min_deposit;
-    }
+    emit!(PoolUpdateProposedEvent {
+        pool: pending_pool_update.pool,
+        authority: pending_pool_update.authority,
+        data: pending_pool_update.data.clone(),
+        activation_epoch: pending_pool_update.activation_epoch,
+        timestamp: clock.unix_timestamp,
+    });
 
     Ok(())
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
-pub struct UpdatePoolData {
-    fee_receiver: Option<Pubkey>,
-    withdraw_fee: Option<u16>,
-    deposit_fee: Option<u16>,
-    mint_fee: Option<u16>,
-    storage_fee: Option<u16>,
-    min_deposit: Option<u64>,
-}
-
 #[derive(Accounts)]
 pub struct UpdatePool<'info> {
-    #[account(mut)]
     pub pool: Box<Account<'info, Pool>>,
 
-    #[account(mut, seeds = [Manager::SEED, authority.key().as_ref()], bump)]
+    #[account(
+        init,
+        seeds = [PendingPoolUpdate::SEED, pool.key().as_ref()],
+        bump,
+        payer = authority,
+        space = PendingPoolUpdate::SIZE,
+    )]
+    pub pending_pool_update: Box<Account<'info, PendingPoolUpdate>>,
+
+    #[account(
+        mut,
+        seeds = [Manager::SEED, authority.key().as_ref()],
+        bump,
+        constraint = manager.has_permission(Manager::FEES)
+    )]
     pub manager: Box<Account<'info, Manager>>,
 
     #[account(mut)]
     pub authority: Signer<'info>,
+
+    pub clock: Sysvar<'info, Clock>,
+    pub system_program: Program<'info, System>,
 }
@@ -1,11 +1,14 @@
 use anchor_lang::{prelude::*, system_program};
-use anchor_spl::token;
+use anchor_spl::{associated_token::get_associated_token_address, token};
 
 use crate::{
     events::*,
-    state::{Collateral, Pool, User},
+    state::{Collateral, FeeMode, FeeType, Oracle, Pool, QueueMember, Treasury, User, Whitelist},
     utils,
-    utils::fee::get_storage_fee,
+    utils::{
+        fee::{calc_fee, record_fee},
+        stake_pool::{get_lamports_value, get_stake_pool},
+    },
     ErrorCode,
 };
 
@@ -14,6 +17,10 @@ use crate::{
 /// Caller provides some [amount] of lp-token-lamports that are to be withdrawn.
 /// Caller provides [with_burn] flag that indicates the priority of withdrawal.
 /// If [with_burn] is true, than firstly all possible omniSol will be burned (in equivalent of withdrawal amount).
+/// LP tokens are valued in lamports by the exchange rate of the whitelisted stake pool,
+/// the rest of the collateral has to cover the rest of the minted omniSOL.
+/// With in-kind fees the withdraw and storage fees are retained in LP tokens and sent to the fee receiver,
+/// SOL fees are charged in the value of these LP tokens.
 pub fn handle(ctx: Context<WithdrawLPTokens>, amount: u64, with_burn: bool) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
 
@@ -28,10 +35,33 @@ pub fn handle(ctx: Context<WithdrawLPTokens>, amount: u64, with_burn: bool) -> R
     }
 
     let collateral = &mut ctx.accounts.collateral;
+    let stake_pool = get_stake_pool(&ctx.accounts.stake_pool, &ctx.accounts.whitelist)?;
 
-    let rest_amount = collateral.delegation_stake - collateral.liquidated_amount;
+    let rest_amount = collateral.get_rest_amount();
 
-    if amount == 0 || with_burn && amount > rest_amount || !with_burn && amount > rest_amount - collateral.amount {
+    if amount == 0 || amount > rest_amount {
+        return Err(ErrorCode::InsufficientAmount.into());
+    }
+
+    let lamports = get_lamports_value(&stake_pool, amount)?;
+    let rest_value = get_lamports_value(&stake_pool, rest_amount - amount)?;
+
+    // partial withdrawal can't leave dust in the collateral
+    if amount < rest_amount && rest_value < pool.min_deposit {
+        msg!(
+            "Rest of the collateral can't be less than {} lamports",
+            pool.min_deposit
+        );
+        return Err(ErrorCode::DepositBelowMinimum.into());
+    }
+
+    let burn_amount = if with_burn { lamports.min(collateral.amount) } else { 0 };
+
+    if rest_value < collateral.amount - burn_amount {
+        msg!(
+            "Rest of the collateral has to cover {} minted lamports",
+            collateral.amount - burn_amount
+        );
         return Err(ErrorCode::InsufficientAmount.into());
     }
 
@@ -39,23 +69,85 @@ pub fn handle(ctx: Context<WithdrawLPTokens>, amount: u64, with_burn: bool) -> R
     let pool_authority_seeds = [pool_key.as_ref(), &[pool.authority_bump]];
     let clock = &ctx.accounts.clock;
 
-    if pool.withdraw_fee > 0 {
-        let fee = amount.saturating_div(1000).saturating_mul(pool.withdraw_fee as u64);
-        msg!("Transfer withdraw fee: {} lamports", fee);
-
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.fee_payer.to_account_info(),
-                    to: ctx.accounts.fee_receiver.to_account_info(),
-                },
-            ),
-            fee,
-        )
-        .map_err(|_| ErrorCode::InsufficientFunds)?;
+    let withdraw_fee = calc_fee(amount, pool.withdraw_fee);
+
+    collateral.settle_storage_fee(pool.storage_fee, clock.epoch);
+    let storage_fee = collateral.take_storage_fee(amount);
+
+    let fee = withdraw_fee.saturating_add(storage_fee).min(amount);
+
+    // SOL fees are charged in the value of LP tokens
+    let fee_mode = pool.fee_mode;
+    let to_fee_units = |fee: u64| match fee_mode {
+        FeeMode::Sol => get_lamports_value(&stake_pool, fee),
+        FeeMode::InKind => Ok(fee),
+    };
+
+    if fee > 0 {
+        msg!("Withdraw fee: {}, storage fee: {}", withdraw_fee, storage_fee);
+
+        match pool.fee_mode {
+            FeeMode::Sol => {
+                let fee = to_fee_units(fee)?;
+                msg!("Transfer withdraw fee: {} lamports", fee);
+
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.fee_payer.to_account_info(),
+                            to: ctx.accounts.treasury.to_account_info(),
+                        },
+                    ),
+                    fee,
+                )
+                .map_err(|_| ErrorCode::InsufficientFunds)?;
+            }
+            FeeMode::InKind => {
+                if ctx.accounts.fee_receiver_lp_token.key()
+                    != get_associated_token_address(&pool.fee_receiver, &ctx.accounts.lp_token.key())
+                {
+                    return Err(ErrorCode::InvalidFeeReceiver.into());
+                }
+
+                msg!("Retain withdraw fee: {} LP tokens", fee);
+
+                token::transfer(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        token::Transfer {
+                            from: ctx.accounts.source.to_account_info(),
+                            to: ctx.accounts.fee_receiver_lp_token.to_account_info(),
+                            authority: ctx.accounts.pool_authority.to_account_info(),
+                        },
+                        &[&pool_authority_seeds],
+                    ),
+                    fee,
+                )?;
+            }
+        }
     }
 
+    record_fee(
+        &mut ctx.accounts.treasury,
+        FeeType::Withdraw,
+        pool.fee_mode,
+        to_fee_units(withdraw_fee)?,
+        clock.unix_timestamp,
+    );
+    record_fee(
+        &mut ctx.accounts.treasury,
+        FeeType::Storage,
+        pool.fee_mode,
+        to_fee_units(storage_fee)?,
+        clock.unix_timestamp,
+    );
+
+    let user_amount = match pool.fee_mode {
+        FeeMode::Sol => amount,
+        FeeMode::InKind => amount - fee,
+    };
+
     // Transfer LP tokens to the user
     token::transfer(
         CpiContext::new_with_signer(
@@ -67,18 +159,10 @@ pub fn handle(ctx: Context<WithdrawLPTokens>, amount: u64, with_burn: bool) -> R
             },
             &[&pool_authority_seeds],
         ),
-        amount,
+        user_amount,
     )?;
 
-    let mut burn_amount = 0;
-
-    if with_burn {
-        burn_amount = if amount > collateral.amount {
-            collateral.amount
-        } else {
-            amount
-        };
-
+    if burn_amount > 0 {
         token::burn(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
@@ -94,32 +178,25 @@ pub fn handle(ctx: Context<WithdrawLPTokens>, amount: u64, with_burn: bool) -> R
         collateral.amount -= burn_amount;
     }
 
-    if pool.storage_fee > 0 {
-        let fee = get_storage_fee(pool.storage_fee as u64, clock.epoch, collateral.creation_epoch, amount);
-        msg!("Transfer storage fee: {} lamports", fee);
-
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.fee_payer.to_account_info(),
-                    to: ctx.accounts.fee_receiver.to_account_info(),
-                },
-            ),
-            fee,
-        )
-        .map_err(|_| ErrorCode::InsufficientFunds)?;
-    }
-
     collateral.delegation_stake -= amount;
-    user.rate -= amount - burn_amount;
+    // the rate is kept in lamports, so it can't underflow after the LP token appreciation
+    user.rate = user.rate.saturating_sub(lamports - burn_amount);
 
     pool.deposit_amount = pool
         .deposit_amount
         .checked_sub(amount)
         .ok_or(ErrorCode::InsufficientAmount)?;
 
-    if collateral.delegation_stake == collateral.liquidated_amount && collateral.amount == collateral.delegation_stake {
+    let oracle = &mut ctx.accounts.oracle;
+    oracle.set_rate(user.key(), user.rate);
+    oracle.upsert(QueueMember {
+        collateral: collateral.key(),
+        user: user.key(),
+        amount: rest_value,
+        rate: user.rate,
+    });
+
+    if collateral.is_closable(get_lamports_value(&stake_pool, collateral.delegation_stake)?) {
         // close the collateral account
         utils::close(collateral.to_account_info(), ctx.accounts.authority.to_account_info())?;
 
@@ -131,6 +208,8 @@ pub fn handle(ctx: Context<WithdrawLPTokens>, amount: u64, with_burn: bool) -> R
         collateral: collateral.key(),
         amount,
         rest_amount: collateral.delegation_stake - collateral.liquidated_amount,
+        fee,
+        fee_mode: pool.fee_mode,
         timestamp: clock.unix_timestamp,
     });
 
@@ -148,22 +227,21 @@ pub struct WithdrawLPTokens<'info> {
 
     #[account(
         mut,
-        seeds = [User::SEED, authority.key().as_ref()],
+        seeds = [User::SEED, pool.key().as_ref(), authority.key().as_ref()],
         bump,
     )]
     pub user: Box<Account<'info, User>>,
 
     #[account(
         mut,
-        seeds = [
-            Collateral::SEED,
-            user.key().as_ref(),
-            lp_token.key().as_ref()
-        ],
-        bump,
+        has_one = user,
+        constraint = collateral.stake_source == lp_token.key(),
     )]
     pub collateral: Box<Account<'info, Collateral>>,
 
+    #[account(mut, seeds = [Oracle::SEED], bump)]
+    pub oracle: Box<Account<'info, Oracle>>,
+
     #[account(
         mut,
         associated_token::mint = lp_token,
@@ -178,9 +256,18 @@ pub struct WithdrawLPTokens<'info> {
     )]
     pub destination: Account<'info, token::TokenAccount>,
 
-    /// CHECK: Address of a token
+    #[account(
+        seeds = [Whitelist::SEED, lp_token.key().as_ref()],
+        bump,
+    )]
+    pub whitelist: Box<Account<'info, Whitelist>>,
+
+    /// CHECK: Address of a token, will be checked via collateral
     pub lp_token: AccountInfo<'info>,
 
+    /// CHECK: SPL stake pool of the LP token, will be checked via whitelist
+    pub stake_pool: AccountInfo<'info>,
+
     /// CHECK:
     #[account(mut, address = pool.pool_mint)]
     pub pool_mint: AccountInfo<'info>,
@@ -198,9 +285,12 @@ pub struct WithdrawLPTokens<'info> {
     #[account(mut)]
     pub fee_payer: Signer<'info>,
 
-    /// CHECK: no needs to check, only for transfer
-    #[account(mut, address = pool.fee_receiver)]
-    pub fee_receiver: AccountInfo<'info>,
+    #[account(mut, seeds = [Treasury::SEED, pool.key().as_ref()], bump = treasury.bump)]
+    pub treasury: Box<Account<'info, Treasury>>,
+
+    /// CHECK: LP token account of the fee receiver, checked only with in-kind fees
+    #[account(mut)]
+    pub fee_receiver_lp_token: AccountInfo<'info>,
 
     pub clock: Sysvar<'info, Clock>,
     pub token_program: Program<'info, token::Token>,
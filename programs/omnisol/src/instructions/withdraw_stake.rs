@@ -3,8 +3,13 @@ use anchor_spl::token;
 
 use crate::{
     events::*,
-    state::{Collateral, Pool, User},
-    utils::{self, fee::get_storage_fee, stake},
+    state::{Collateral, FeeMode, FeeType, Oracle, Pool, QueueMember, Treasury, User, Validator},
+    utils::{
+        self,
+        fee::{calc_fee, record_fee},
+        stake,
+        validator::load_validator,
+    },
     ErrorCode,
 };
 
@@ -15,6 +20,9 @@ use crate::{
 /// If [with_burn] is true, than firstly all possible omniSol will be burned (in equivalent of withdrawal amount).
 /// Caller provides [with_merge] flag that indicates the possibility to merge.
 /// If [with_merge] is true, than delegated stake account or split stake of it will be merged with the source one.
+/// With in-kind fees the withdraw and storage fees are split off the collateral stake to a fee stake account,
+/// that is authorized to the fee receiver, so the user doesn't need SOL to pay them.
+/// A fee below the rent-exempt reserve of a stake account can't be split off, so it's paid in SOL.
 pub fn handle(ctx: Context<WithdrawStake>, amount: u64, with_burn: bool, with_merge: bool) -> Result<()> {
     let pool = &mut ctx.accounts.pool;
 
@@ -30,33 +38,128 @@ pub fn handle(ctx: Context<WithdrawStake>, amount: u64, with_burn: bool, with_me
 
     let collateral = &mut ctx.accounts.collateral;
 
+    if collateral.pending_vote_account.is_some() {
+        msg!("Collateral stake is being redelegated");
+        return Err(ErrorCode::StakeNotActive.into());
+    }
+
     let rest_amount = collateral.delegation_stake - collateral.liquidated_amount;
 
     if amount == 0 || with_burn && amount > rest_amount || !with_burn && amount > rest_amount - collateral.amount {
         return Err(ErrorCode::InsufficientAmount.into());
     }
 
+    // partial withdrawal can't leave dust in the collateral
+    if amount < rest_amount && rest_amount - amount < pool.min_deposit {
+        msg!("Rest of the collateral can't be less than {}", pool.min_deposit);
+        return Err(ErrorCode::DepositBelowMinimum.into());
+    }
+
     let pool_key = pool.key();
     let pool_authority_seeds = [pool_key.as_ref(), &[pool.authority_bump]];
     let clock = &ctx.accounts.clock;
 
-    if pool.withdraw_fee > 0 {
-        let fee = amount.saturating_div(1000).saturating_mul(pool.withdraw_fee as u64);
-        msg!("Transfer withdraw fee: {} lamports", fee);
+    let withdraw_fee = calc_fee(amount, pool.withdraw_fee);
 
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.fee_payer.to_account_info(),
-                    to: ctx.accounts.fee_receiver.to_account_info(),
-                },
-            ),
-            fee,
-        )
-        .map_err(|_| ErrorCode::InsufficientFunds)?;
+    collateral.settle_storage_fee(pool.storage_fee, clock.epoch);
+    let storage_fee = collateral.take_storage_fee(amount);
+
+    let fee = withdraw_fee.saturating_add(storage_fee).min(amount);
+
+    let rent_exempt_reserve = ctx
+        .accounts
+        .delegated_stake
+        .meta()
+        .map(|meta| meta.rent_exempt_reserve)
+        .unwrap_or_default();
+    let fee_mode = match pool.fee_mode {
+        FeeMode::InKind if fee < rent_exempt_reserve => FeeMode::Sol,
+        fee_mode => fee_mode,
+    };
+
+    if fee > 0 {
+        msg!(
+            "Withdraw fee: {} lamports, storage fee: {} lamports",
+            withdraw_fee,
+            storage_fee
+        );
+
+        match fee_mode {
+            FeeMode::Sol => {
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.fee_payer.to_account_info(),
+                            to: ctx.accounts.treasury.to_account_info(),
+                        },
+                    ),
+                    fee,
+                )
+                .map_err(|_| ErrorCode::InsufficientFunds)?;
+            }
+            FeeMode::InKind => {
+                if fee == amount {
+                    return Err(ErrorCode::InsufficientAmount.into());
+                }
+
+                // Split the fee from the delegated stake to the fee stake account
+                stake::split(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.stake_program.to_account_info(),
+                        stake::Split {
+                            stake: ctx.accounts.delegated_stake.to_account_info(),
+                            split_stake: ctx.accounts.fee_stake.to_account_info(),
+                            authority: ctx.accounts.pool_authority.to_account_info(),
+                            system_program: ctx.accounts.system_program.to_account_info(),
+                        },
+                        &[&pool_authority_seeds],
+                    ),
+                    fee,
+                )?;
+
+                for stake_authorize in [StakeAuthorize::Withdrawer, StakeAuthorize::Staker] {
+                    stake::authorize(
+                        CpiContext::new_with_signer(
+                            ctx.accounts.stake_program.to_account_info(),
+                            stake::Authorize {
+                                stake: ctx.accounts.fee_stake.to_account_info(),
+                                authority: ctx.accounts.pool_authority.to_account_info(),
+                                new_authority: ctx.accounts.fee_receiver.to_account_info(),
+                                clock: clock.to_account_info(),
+                            },
+                            &[&pool_authority_seeds],
+                        ),
+                        stake_authorize,
+                        None,
+                    )?;
+                }
+            }
+        }
     }
 
+    record_fee(
+        &mut ctx.accounts.treasury,
+        FeeType::Withdraw,
+        fee_mode,
+        withdraw_fee,
+        clock.unix_timestamp,
+    );
+    record_fee(
+        &mut ctx.accounts.treasury,
+        FeeType::Storage,
+        fee_mode,
+        storage_fee,
+        clock.unix_timestamp,
+    );
+
+    // the split fee stake has left the collateral stake already
+    let user_amount = match fee_mode {
+        FeeMode::Sol => amount,
+        FeeMode::InKind => amount - fee,
+    };
+    let burn_amount = collateral.withdraw(amount, with_burn);
+
     let source_stake = if amount < rest_amount {
         stake::split(
             CpiContext::new_with_signer(
@@ -69,7 +172,7 @@ pub fn handle(ctx: Context<WithdrawStake>, amount: u64, with_burn: bool, with_me
                 },
                 &[&pool_authority_seeds],
             ),
-            amount,
+            user_amount,
         )?;
         ctx.accounts.split_stake.to_account_info()
     } else {
@@ -134,15 +237,7 @@ pub fn handle(ctx: Context<WithdrawStake>, amount: u64, with_burn: bool, with_me
         ))?;
     }
 
-    let mut burn_amount = 0;
-
-    if with_burn {
-        burn_amount = if amount > collateral.amount {
-            collateral.amount
-        } else {
-            amount
-        };
-
+    if burn_amount > 0 {
         token::burn(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
@@ -154,28 +249,8 @@ pub fn handle(ctx: Context<WithdrawStake>, amount: u64, with_burn: bool, with_me
             ),
             burn_amount,
         )?;
-
-        collateral.amount -= burn_amount;
-    }
-
-    if pool.storage_fee > 0 {
-        let fee = get_storage_fee(pool.storage_fee as u64, clock.epoch, collateral.creation_epoch, amount);
-        msg!("Transfer storage fee: {} lamports", fee);
-
-        system_program::transfer(
-            CpiContext::new(
-                ctx.accounts.system_program.to_account_info(),
-                system_program::Transfer {
-                    from: ctx.accounts.fee_payer.to_account_info(),
-                    to: ctx.accounts.fee_receiver.to_account_info(),
-                },
-            ),
-            fee,
-        )
-        .map_err(|_| ErrorCode::InsufficientFunds)?;
     }
 
-    collateral.delegation_stake -= amount;
     user.rate -= amount - burn_amount;
 
     pool.deposit_amount = pool
@@ -183,7 +258,21 @@ pub fn handle(ctx: Context<WithdrawStake>, amount: u64, with_burn: bool, with_me
         .checked_sub(amount)
         .ok_or(ErrorCode::InsufficientAmount)?;
 
-    if collateral.delegation_stake == collateral.liquidated_amount && collateral.amount == collateral.delegation_stake {
+    if let Some(mut validator) = load_validator(&ctx.accounts.validator)? {
+        validator.collateral_amount = validator.collateral_amount.saturating_sub(amount);
+        validator.exit(&crate::ID)?;
+    }
+
+    let oracle = &mut ctx.accounts.oracle;
+    oracle.set_rate(user.key(), user.rate);
+    oracle.upsert(QueueMember {
+        collateral: collateral.key(),
+        user: user.key(),
+        amount: collateral.get_rest_amount(),
+        rate: user.rate,
+    });
+
+    if collateral.is_closable(collateral.delegation_stake) {
         // close the collateral account
         utils::close(collateral.to_account_info(), ctx.accounts.authority.to_account_info())?;
 
@@ -196,6 +285,8 @@ pub fn handle(ctx: Context<WithdrawStake>, amount: u64, with_burn: bool, with_me
         timestamp: clock.unix_timestamp,
         rest_amount: collateral.delegation_stake - collateral.liquidated_amount,
         amount,
+        fee,
+        fee_mode,
     });
 
     Ok(())
@@ -212,22 +303,17 @@ pub struct WithdrawStake<'info> {
 
     #[account(
         mut,
-        seeds = [User::SEED, authority.key().as_ref()],
+        seeds = [User::SEED, pool.key().as_ref(), authority.key().as_ref()],
         bump,
     )]
     pub user: Box<Account<'info, User>>,
 
-    #[account(
-        mut,
-        seeds = [
-            Collateral::SEED,
-            user.key().as_ref(),
-            delegated_stake.key().as_ref()
-        ],
-        bump,
-    )]
+    #[account(mut, has_one = user)]
     pub collateral: Account<'info, Collateral>,
 
+    #[account(mut, seeds = [Oracle::SEED], bump)]
+    pub oracle: Box<Account<'info, Oracle>>,
+
     /// CHECK:
     #[account(mut, address = pool.pool_mint)]
     pub pool_mint: AccountInfo<'info>,
@@ -238,6 +324,18 @@ pub struct WithdrawStake<'info> {
     #[account(mut, constraint = collateral.delegated_stake == delegated_stake.key())]
     pub delegated_stake: Box<Account<'info, stake::StakeAccount>>,
 
+    /// CHECK: validator entry of the delegated stake's vote account, may be uninitialized
+    #[account(
+        mut,
+        seeds = [
+            Validator::SEED,
+            pool.key().as_ref(),
+            delegated_stake.delegation().map(|d| d.voter_pubkey).unwrap_or_default().as_ref(),
+        ],
+        bump,
+    )]
+    pub validator: AccountInfo<'info>,
+
     /// CHECK: optional stake account to merge
     #[account(mut)]
     pub mergable_stake: Box<Account<'info, stake::StakeAccount>>,
@@ -259,10 +357,17 @@ pub struct WithdrawStake<'info> {
     #[account(mut)]
     pub fee_payer: Signer<'info>,
 
-    /// CHECK: no needs to check, only for transfer
-    #[account(mut, address = pool.fee_receiver)]
+    /// CHECK: new stake account for the fee, has to sign only when the in-kind fee is split off
+    #[account(mut)]
+    pub fee_stake: AccountInfo<'info>,
+
+    /// CHECK: no needs to check, only as the fee stake authority
+    #[account(address = pool.fee_receiver)]
     pub fee_receiver: AccountInfo<'info>,
 
+    #[account(mut, seeds = [Treasury::SEED, pool.key().as_ref()], bump = treasury.bump)]
+    pub treasury: Box<Account<'info, Treasury>>,
+
     pub clock: Sysvar<'info, Clock>,
     pub stake_history: Sysvar<'info, StakeHistory>,
     pub stake_program: Program<'info, stake::Stake>,
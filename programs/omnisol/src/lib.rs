@@ -7,7 +7,10 @@ mod utils;
 
 use anchor_lang::prelude::*;
 
-use crate::instructions::*;
+use crate::{
+    instructions::*,
+    state::{FeeRecipient, LiquidationRoute, QueueMember, UpdatePoolData, WithdrawMode},
+};
 
 declare_id!("DMG9gp5VHPVpA3bst6yhC4L4D4aZiUjUTibVQGvJzpjy");
 
@@ -19,8 +22,24 @@ pub mod omnisol {
         init_pool::handle(ctx)
     }
 
-    pub fn add_manager(ctx: Context<AddManager>) -> Result<()> {
-        add_manager::handle(ctx)
+    pub fn init_global_config(ctx: Context<InitGlobalConfig>) -> Result<()> {
+        init_global_config::handle(ctx)
+    }
+
+    pub fn propose_admin(ctx: Context<ProposeAdmin>) -> Result<()> {
+        propose_admin::handle(ctx)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        accept_admin::handle(ctx)
+    }
+
+    pub fn add_manager(ctx: Context<AddManager>, permissions: u8) -> Result<()> {
+        add_manager::handle(ctx, permissions)
+    }
+
+    pub fn set_manager_permissions(ctx: Context<SetManagerPermissions>, permissions: u8) -> Result<()> {
+        set_manager_permissions::handle(ctx, permissions)
     }
 
     pub fn remove_manager(ctx: Context<RemoveManager>) -> Result<()> {
@@ -43,6 +62,14 @@ pub mod omnisol {
         remove_from_whitelist::handle(ctx)
     }
 
+    pub fn set_validator(ctx: Context<SetValidator>, is_allowed: bool, max_collateral: u64) -> Result<()> {
+        set_validator::handle(ctx, is_allowed, max_collateral)
+    }
+
+    pub fn remove_validator(ctx: Context<RemoveValidator>) -> Result<()> {
+        remove_validator::handle(ctx)
+    }
+
     pub fn block_user(ctx: Context<BlockUser>) -> Result<()> {
         block_user::handle(ctx)
     }
@@ -55,15 +82,25 @@ pub mod omnisol {
         close_pool::handle(ctx)
     }
 
-    pub fn deposit_lp(ctx: Context<DepositLPTokens>, amount: u64) -> Result<()> {
+    pub fn deposit_lp<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositLPTokens<'info>>,
+        amount: u64,
+    ) -> Result<()> {
         deposit_lp_tokens::handle(ctx, amount)
     }
 
-    pub fn deposit_stake(ctx: Context<DepositStake>, amount: u64) -> Result<()> {
-        deposit_stake::handle(ctx, amount)
+    pub fn deposit_stake<'info>(
+        ctx: Context<'_, '_, '_, 'info, DepositStake<'info>>,
+        amount: u64,
+        with_merge: bool,
+    ) -> Result<()> {
+        deposit_stake::handle(ctx, amount, with_merge)
     }
 
-    pub fn mint_omnisol(ctx: Context<MintOmnisol>, amount: u64) -> Result<()> {
+    pub fn mint_omnisol<'info>(
+        ctx: Context<'_, '_, '_, 'info, MintOmnisol<'info>>,
+        amount: u64,
+    ) -> Result<()> {
         mint_omnisol::handle(ctx, amount)
     }
 
@@ -75,8 +112,32 @@ pub mod omnisol {
         withdraw_stake::handle(ctx, amount, with_burn, with_merge)
     }
 
-    pub fn burn_omnisol(ctx: Context<BurnOmnisol>, amount: u64) -> Result<()> {
-        burn_omnisol::handle(ctx, amount)
+    pub fn sync_collateral(ctx: Context<SyncCollateral>) -> Result<()> {
+        sync_collateral::handle(ctx)
+    }
+
+    pub fn settle_storage_fee(ctx: Context<SettleStorageFee>) -> Result<()> {
+        settle_storage_fee::handle(ctx)
+    }
+
+    pub fn burn_omnisol<'info>(
+        ctx: Context<'_, '_, '_, 'info, BurnOmnisol<'info>>,
+        amount: u64,
+        mode: WithdrawMode,
+        min_lamports_out: u64,
+    ) -> Result<()> {
+        burn_omnisol::handle(ctx, amount, mode, min_lamports_out)
+    }
+
+    pub fn cancel_withdraw_request<'info>(
+        ctx: Context<'_, '_, '_, 'info, CancelWithdrawRequest<'info>>,
+        index: u32,
+    ) -> Result<()> {
+        cancel_withdraw_request::handle(ctx, index)
+    }
+
+    pub fn claim_withdrawal<'info>(ctx: Context<'_, '_, '_, 'info, ClaimWithdrawal<'info>>) -> Result<()> {
+        claim_withdrawal::handle(ctx)
     }
 
     pub fn init_oracle(ctx: Context<InitOracle>) -> Result<()> {
@@ -87,13 +148,12 @@ pub mod omnisol {
         close_oracle::handle(ctx)
     }
 
-    pub fn update_oracle_info(
-        ctx: Context<UpdateOracleInfo>,
-        addresses: Vec<Pubkey>,
-        values: Vec<u64>,
-        clear: bool,
-    ) -> Result<()> {
-        update_oracle_info::handle(ctx, addresses, values, clear)
+    pub fn update_oracle_info(ctx: Context<UpdateOracleInfo>, members: Vec<QueueMember>, clear: bool) -> Result<()> {
+        update_oracle_info::handle(ctx, members, clear)
+    }
+
+    pub fn commit_oracle_info(ctx: Context<CommitOracleInfo>) -> Result<()> {
+        commit_oracle_info::handle(ctx)
     }
 
     pub fn add_liquidator(ctx: Context<AddLiquidator>) -> Result<()> {
@@ -107,24 +167,85 @@ pub mod omnisol {
     pub fn liquidate_collateral<'info>(
         ctx: Context<'_, '_, '_, 'info, LiquidateCollateral<'info>>,
         amount: u64,
+        route: LiquidationRoute,
+    ) -> Result<()> {
+        liquidate_collateral::handle(ctx, amount, route)
+    }
+
+    pub fn deactivate_for_withdrawal(ctx: Context<DeactivateForWithdrawal>) -> Result<()> {
+        deactivate_for_withdrawal::handle(ctx)
+    }
+
+    pub fn migrate_collateral(ctx: Context<MigrateCollateral>) -> Result<()> {
+        migrate_collateral::handle(ctx)
+    }
+
+    pub fn migrate_liquidation_fee(ctx: Context<MigrateLiquidationFee>) -> Result<()> {
+        migrate_liquidation_fee::handle(ctx)
+    }
+
+    pub fn migrate_manager(ctx: Context<MigrateManager>) -> Result<()> {
+        migrate_manager::handle(ctx)
+    }
+
+    pub fn migrate_oracle(ctx: Context<MigrateOracle>) -> Result<()> {
+        migrate_oracle::handle(ctx)
+    }
+
+    pub fn migrate_pool(ctx: Context<MigratePool>) -> Result<()> {
+        migrate_pool::handle(ctx)
+    }
+
+    pub fn migrate_user<'info>(
+        ctx: Context<'_, '_, '_, 'info, MigrateUser<'info>>,
+        close_legacy: bool,
     ) -> Result<()> {
-        liquidate_collateral::handle(ctx, amount)
+        migrate_user::handle(ctx, close_legacy)
+    }
+
+    pub fn migrate_withdraw_request(ctx: Context<MigrateWithdrawRequest>) -> Result<()> {
+        migrate_withdraw_request::handle(ctx)
     }
 
     pub fn update_pool(ctx: Context<UpdatePool>, data: UpdatePoolData) -> Result<()> {
         update_pool::handle(ctx, data)
     }
 
-    pub fn withdraw_sol(ctx: Context<WithdrawSol>, amount: u64) -> Result<()> {
-        withdraw_sol::handle(ctx, amount)
+    pub fn apply_pool_update(ctx: Context<ApplyPoolUpdate>) -> Result<()> {
+        apply_pool_update::handle(ctx)
+    }
+
+    pub fn cancel_pool_update(ctx: Context<CancelPoolUpdate>) -> Result<()> {
+        cancel_pool_update::handle(ctx)
+    }
+
+    pub fn deactivate_collateral(ctx: Context<DeactivateCollateral>) -> Result<()> {
+        deactivate_collateral::handle(ctx)
+    }
+
+    pub fn redelegate_collateral(ctx: Context<RedelegateCollateral>) -> Result<()> {
+        redelegate_collateral::handle(ctx)
+    }
+
+    pub fn register_referrer(ctx: Context<RegisterReferrer>) -> Result<()> {
+        register_referrer::handle(ctx)
+    }
+
+    pub fn sweep_liquidation_fees(ctx: Context<SweepLiquidationFees>) -> Result<()> {
+        sweep_liquidation_fees::handle(ctx)
+    }
+
+    pub fn sweep_treasury(ctx: Context<SweepTreasury>, amount: u64) -> Result<()> {
+        sweep_treasury::handle(ctx, amount)
     }
 
     pub fn set_liquidation_fee(
         ctx: Context<SetLiquidationFee>,
         fee: Option<u16>,
         fee_receiver: Option<Pubkey>,
+        fee_recipients: Option<Vec<FeeRecipient>>,
     ) -> Result<()> {
-        set_liquidation_fee::handle(ctx, fee, fee_receiver)
+        set_liquidation_fee::handle(ctx, fee, fee_receiver, fee_recipients)
     }
 }
 
@@ -154,4 +275,36 @@ pub enum ErrorCode {
     UserNotBlocked,
     #[msg("Wrong input data")]
     WrongData,
+    #[msg("Invalid stake pool")]
+    InvalidStakePool,
+    #[msg("User has pending withdraw requests")]
+    PendingWithdrawRequests,
+    #[msg("Pool update is still locked")]
+    PoolUpdateIsLocked,
+    #[msg("Amount is below the minimal deposit")]
+    DepositBelowMinimum,
+    #[msg("Amount is below the minimal value")]
+    AmountBelowMinimum,
+    #[msg("Invalid fee receiver")]
+    InvalidFeeReceiver,
+    #[msg("Invalid referrer")]
+    InvalidReferrer,
+    #[msg("Validator is not allowed")]
+    ValidatorNotAllowed,
+    #[msg("Validator collateral cap exceeded")]
+    ValidatorCapExceeded,
+    #[msg("Stake is not fully active")]
+    StakeNotActive,
+    #[msg("Stake is not deactivated yet")]
+    StakeNotDeactivated,
+    #[msg("Invalid withdraw request mode")]
+    InvalidWithdrawMode,
+    #[msg("Withdrawal is not claimable yet")]
+    WithdrawalNotClaimable,
+    #[msg("Received amount is below the minimum")]
+    SlippageExceeded,
+    #[msg("Validator still has collateral")]
+    ValidatorHasCollateral,
+    #[msg("Treasury still has fees")]
+    TreasuryNotEmpty,
 }
@@ -1,8 +1,22 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, solana_program::entrypoint::MAX_PERMITTED_DATA_INCREASE};
+
+use crate::utils::fee::get_storage_fee;
 
-pub const ADMIN: &str = "4kMtMnYWFbsMc7M3jcdnfCceHaiXmrqaMz2QZQAmn88i";
 pub const MINT_AUTHORITY_SEED: &'static [u8] = b"mint_authority";
 
+#[account]
+pub struct GlobalConfig {
+    /// Admin wallet that can manage managers, oracle and liquidation fee
+    pub admin: Pubkey,
+    /// Proposed admin wallet that has to accept the role
+    pub pending_admin: Option<Pubkey>,
+}
+
+impl GlobalConfig {
+    pub const SEED: &'static [u8] = b"global_config";
+    pub const SIZE: usize = 8 + 32 + 1 + 32;
+}
+
 #[account]
 pub struct Pool {
     /// Pool tokens are issued when assets are deposited.
@@ -19,35 +33,306 @@ pub struct Pool {
     pub authority_bump: u8,
     /// Flag that indicates that the pool is running or paused
     pub is_active: bool,
-    /// Wallet that will receive fee
+    /// Wallet that receives in-kind fees and SOL fees swept from the treasury
     pub fee_receiver: Pubkey,
-    /// Fee for withdrawing from pool (in %)
+    /// Fee for withdrawing from pool (in basis points)
     pub withdraw_fee: u16,
-    /// Fee for minting omnisol from pool (in %)
+    /// Fee for minting omnisol from pool (in basis points)
     pub mint_fee: u16,
-    /// Fee for depositing in pool (in %)
+    /// Fee for depositing in pool (in basis points)
     pub deposit_fee: u16,
-    /// Fee for keeping deposit in pool (in %, per epoch)
+    /// Fee for keeping deposit in pool (in basis points, per epoch)
     pub storage_fee: u16,
     /// Minimal deposit amount
     pub min_deposit: u64,
+    /// Minimal amount of omnisol to mint
+    pub min_mint: u64,
+    /// Minimal amount of omnisol to burn
+    pub min_burn: u64,
+    /// Way of charging mint, withdraw and liquidation fees
+    pub fee_mode: FeeMode,
+    /// Wallets that receive their shares of SOL fees swept from the treasury
+    pub fee_recipients: Vec<FeeRecipient>,
+    /// Share of deposit and mint SOL fees that is paid to the referrer (in basis points)
+    pub referral_share: u16,
+    /// Validators that native stake deposits can be delegated to
+    pub validator_policy: ValidatorPolicy,
 }
 
 impl Pool {
-    pub const SIZE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1 + 1 + 32 + 2 + 2 + 2 + 2 + 8;
+    pub const SIZE: usize =
+        8 + 32 + 32 + 32 + 8 + 8 + 1 + 1 + 32 + 2 + 2 + 2 + 2 + 8 + 8 + 8 + 1 + FeeRecipient::VEC_SIZE + 2 + 1;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeRecipient {
+    /// Wallet that receives the fee share
+    pub wallet: Pubkey,
+    /// Share of the fee (in basis points)
+    pub share: u16,
+}
+
+impl FeeRecipient {
+    pub const SIZE: usize = 32 + 2;
+    pub const MAX_RECIPIENTS: usize = 5;
+    /// Size of the serialized list of recipients with max length
+    pub const VEC_SIZE: usize = 4 + Self::MAX_RECIPIENTS * Self::SIZE;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeeMode {
+    /// Fees are transferred in SOL from the fee payer
+    Sol,
+    /// Fees are withheld from the pool's own asset:
+    /// omniSOL on mint and burn, LP tokens and stake on withdrawal
+    InKind,
+}
+
+impl Default for FeeMode {
+    fn default() -> Self {
+        Self::Sol
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValidatorPolicy {
+    /// Stake delegated to any validator is accepted
+    Any,
+    /// Only stake delegated to validators with allowing entries is accepted
+    Allowlist,
+    /// Stake delegated to validators with denying entries is rejected
+    Denylist,
+}
+
+impl Default for ValidatorPolicy {
+    fn default() -> Self {
+        Self::Any
+    }
+}
+
+impl ValidatorPolicy {
+    /// Checks if the stake delegated to the validator with the [entry] can be deposited.
+    pub fn allows(&self, entry: Option<&Validator>) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Allowlist => matches!(entry, Some(validator) if validator.is_allowed),
+            Self::Denylist => !matches!(entry, Some(validator) if !validator.is_allowed),
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct UpdatePoolData {
+    pub fee_receiver: Option<Pubkey>,
+    pub withdraw_fee: Option<u16>,
+    pub deposit_fee: Option<u16>,
+    pub mint_fee: Option<u16>,
+    pub storage_fee: Option<u16>,
+    pub min_deposit: Option<u64>,
+    pub min_mint: Option<u64>,
+    pub min_burn: Option<u64>,
+    pub fee_mode: Option<FeeMode>,
+    pub fee_recipients: Option<Vec<FeeRecipient>>,
+    pub referral_share: Option<u16>,
+    pub validator_policy: Option<ValidatorPolicy>,
+}
+
+impl UpdatePoolData {
+    pub const SIZE: usize = 33 + 3 + 3 + 3 + 3 + 9 + 9 + 9 + 2 + 1 + FeeRecipient::VEC_SIZE + 3 + 2;
+
+    pub fn apply(&self, pool: &mut Pool) {
+        if let Some(fee_receiver) = self.fee_receiver {
+            pool.fee_receiver = fee_receiver;
+        }
+        if let Some(withdraw_fee) = self.withdraw_fee {
+            pool.withdraw_fee = withdraw_fee;
+        }
+        if let Some(deposit_fee) = self.deposit_fee {
+            pool.deposit_fee = deposit_fee;
+        }
+        if let Some(mint_fee) = self.mint_fee {
+            pool.mint_fee = mint_fee;
+        }
+        if let Some(storage_fee) = self.storage_fee {
+            pool.storage_fee = storage_fee;
+        }
+        if let Some(min_deposit) = self.min_deposit {
+            pool.min_deposit = min_deposit;
+        }
+        if let Some(min_mint) = self.min_mint {
+            pool.min_mint = min_mint;
+        }
+        if let Some(min_burn) = self.min_burn {
+            pool.min_burn = min_burn;
+        }
+        if let Some(fee_mode) = self.fee_mode {
+            pool.fee_mode = fee_mode;
+        }
+        if let Some(fee_recipients) = &self.fee_recipients {
+            pool.fee_recipients = fee_recipients.clone();
+        }
+        if let Some(referral_share) = self.referral_share {
+            pool.referral_share = referral_share;
+        }
+        if let Some(validator_policy) = self.validator_policy {
+            pool.validator_policy = validator_policy;
+        }
+    }
+}
+
+#[account]
+pub struct PendingPoolUpdate {
+    /// Pool to update
+    pub pool: Pubkey,
+    /// Manager that proposed the update
+    pub authority: Pubkey,
+    /// New pool parameters
+    pub data: UpdatePoolData,
+    /// Epoch since which the update can be applied
+    pub activation_epoch: u64,
+    /// Time of the update proposal
+    pub created_at: i64,
+}
+
+impl PendingPoolUpdate {
+    pub const SEED: &'static [u8] = b"pending_pool_update";
+    pub const SIZE: usize = 8 + 32 + 32 + UpdatePoolData::SIZE + 8 + 8;
+    /// Amount of epochs that users have to react on the proposed update,
+    /// an update proposed at the end of an epoch still leaves them a full epoch
+    pub const DELAY_EPOCHS: u64 = 2;
 }
 
 #[account]
 pub struct LiquidationFee {
     /// Wallet that will receive fee
     pub fee_receiver: Pubkey,
-    /// Fee for creating liquidation request
+    /// Fee for creating liquidation request (in basis points)
     pub fee: u16,
+    /// Wallets that receive their shares of the fee, the rest goes to the fee receiver
+    pub fee_recipients: Vec<FeeRecipient>,
 }
 
 impl LiquidationFee {
     pub const SEED: &'static [u8] = b"liquidation_fee";
-    pub const SIZE: usize = 8 + 32 + 2;
+    pub const SIZE: usize = 8 + 32 + 2 + FeeRecipient::VEC_SIZE;
+}
+
+#[account]
+pub struct Referral {
+    /// Wallet of the referrer
+    pub referrer: Pubkey,
+    /// Pool the referrer is registered in
+    pub pool: Pubkey,
+    /// Total fees paid to the referrer
+    pub total_fees: u64,
+    /// Signer bump seed for deriving PDA seeds
+    pub bump: u8,
+}
+
+impl Referral {
+    pub const SEED: &'static [u8] = b"referral";
+    pub const SIZE: usize = 8 + 32 + 32 + 8 + 1;
+}
+
+#[account]
+pub struct Validator {
+    /// Pool the entry belongs to
+    pub pool: Pubkey,
+    /// Vote account of the validator
+    pub vote_account: Pubkey,
+    /// Flag that indicates that the validator is allowed or denied by the pool's policy
+    pub is_allowed: bool,
+    /// Max amount of lamports deposited with the validator (zero means unlimited)
+    pub max_collateral: u64,
+    /// Current amount of lamports deposited with the validator
+    pub collateral_amount: u64,
+    /// Signer bump seed for deriving PDA seeds
+    pub bump: u8,
+}
+
+impl Validator {
+    pub const SEED: &'static [u8] = b"validator";
+    pub const SIZE: usize = 8 + 32 + 32 + 1 + 8 + 8 + 1;
+
+    /// Checks if the [amount] can be deposited without exceeding the cap.
+    pub fn has_capacity(&self, amount: u64) -> bool {
+        self.max_collateral == 0 || self.collateral_amount.saturating_add(amount) <= self.max_collateral
+    }
+}
+
+#[account]
+pub struct Treasury {
+    /// Pool that the treasury belongs to
+    pub pool: Pubkey,
+    /// Total collected deposit fees
+    pub deposit_fees: u64,
+    /// Total collected mint fees
+    pub mint_fees: u64,
+    /// Total collected withdraw fees
+    pub withdraw_fees: u64,
+    /// Total collected storage fees
+    pub storage_fees: u64,
+    /// Total collected liquidation fees
+    pub liquidation_fees: u64,
+    /// Total amount of lamports swept from the treasury
+    pub swept_amount: u64,
+    /// Signer bump seed for deriving PDA seeds
+    pub bump: u8,
+    /// SOL liquidation fees that are kept for the liquidation fee receivers until they are swept
+    pub liquidation_fee_balance: u64,
+    /// In-kind liquidation fees that are minted as omniSOL to the liquidation fee receiver when they are swept
+    pub in_kind_liquidation_fee_balance: u64,
+    /// SOL liquidation fees of pending withdraw requests, that are refunded if the requests are canceled
+    pub escrowed_liquidation_fees: u64,
+}
+
+impl Treasury {
+    pub const SEED: &'static [u8] = b"treasury";
+    pub const SIZE: usize = 8 + 32 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 8 + 8 + 8;
+
+    /// Records collected fee.
+    /// SOL fees are counted in lamports, in-kind fees in units of the withheld asset.
+    pub fn accrue(&mut self, fee_type: FeeType, amount: u64) {
+        let total = match fee_type {
+            FeeType::Deposit => &mut self.deposit_fees,
+            FeeType::Mint => &mut self.mint_fees,
+            FeeType::Withdraw => &mut self.withdraw_fees,
+            FeeType::Storage => &mut self.storage_fees,
+            FeeType::Liquidation => &mut self.liquidation_fees,
+        };
+        *total = total.saturating_add(amount);
+    }
+
+    /// Releases the escrowed liquidation fee of the fulfilled part of the withdraw request
+    /// to the liquidation fee receivers.
+    pub fn release_liquidation_fee(&mut self, fee_mode: FeeMode, amount: u64) {
+        match fee_mode {
+            FeeMode::Sol => {
+                self.escrowed_liquidation_fees = self.escrowed_liquidation_fees.saturating_sub(amount);
+                self.liquidation_fee_balance = self.liquidation_fee_balance.saturating_add(amount);
+            }
+            FeeMode::InKind => {
+                self.in_kind_liquidation_fee_balance = self.in_kind_liquidation_fee_balance.saturating_add(amount);
+            }
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LiquidationRoute {
+    /// Native stake is instantly unstaked with unstake.it
+    UnstakeIt,
+    /// Native stake is deposited into a whitelisted SPL stake pool and its LP tokens are withdrawn as SOL
+    StakePool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeeType {
+    Deposit,
+    Mint,
+    Withdraw,
+    Storage,
+    Liquidation,
 }
 
 #[account]
@@ -56,19 +341,92 @@ pub struct Oracle {
     pub authority: Pubkey,
     /// Priority queue with collaterals by users rate in ascending order
     pub priority_queue: Vec<QueueMember>,
+    /// Queue that is filled by the oracle and becomes active after commit
+    pub staging_queue: Vec<QueueMember>,
 }
 
 impl Oracle {
     pub const SEED: &'static [u8] = b"oracle";
-    pub const SIZE: usize = 4068;
+    pub const SIZE: usize = 8 + 32 + 4 + 4 + 2 * Self::MAX_PRIORITY_QUEUE_LENGTH * QueueMember::SIZE;
+    /// Accounts created via CPI are limited to 10 KiB, so the rest is reallocated by `update_oracle_info`
+    pub const INIT_SIZE: usize = MAX_PERMITTED_DATA_INCREASE;
     pub const MAX_PRIORITY_QUEUE_LENGTH: usize = 100;
     pub const MAX_BATCH_LENGTH: usize = 25;
+
+    /// Sets the new [rate] for all queue members of the [user] and restores the queue order.
+    pub fn set_rate(&mut self, user: Pubkey, rate: u64) {
+        set_queue_rate(&mut self.priority_queue, user, rate);
+        set_queue_rate(&mut self.staging_queue, user, rate);
+    }
+
+    /// Inserts or updates the queue member by its collateral, keeping the queue sorted by rate.
+    /// Members with zero amount are removed, members that don't fit into the queue are skipped.
+    /// The staging queue is updated as well, so the next commit doesn't roll the change back.
+    pub fn upsert(&mut self, queue_member: QueueMember) {
+        upsert_queue_member(&mut self.priority_queue, queue_member.clone());
+        upsert_queue_member(&mut self.staging_queue, queue_member);
+    }
+
+    /// Removes the queue member of the [collateral] if it exists.
+    pub fn remove(&mut self, collateral: &Pubkey) {
+        self.priority_queue.retain(|m| m.collateral != *collateral);
+        self.staging_queue.retain(|m| m.collateral != *collateral);
+    }
+
+    /// Inserts or updates the queue member in the staging queue only.
+    pub fn stage(&mut self, queue_member: QueueMember) {
+        upsert_queue_member(&mut self.staging_queue, queue_member);
+    }
+
+    /// Makes the staging queue active and clears the previous one.
+    pub fn commit(&mut self) {
+        std::mem::swap(&mut self.priority_queue, &mut self.staging_queue);
+        self.staging_queue.clear();
+    }
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+fn set_queue_rate(queue: &mut [QueueMember], user: Pubkey, rate: u64) {
+    let mut changed = false;
+    for queue_member in queue.iter_mut().filter(|m| m.user == user) {
+        queue_member.rate = rate;
+        changed = true;
+    }
+    if changed {
+        queue.sort_by_key(|m| m.rate);
+    }
+}
+
+fn upsert_queue_member(queue: &mut Vec<QueueMember>, queue_member: QueueMember) {
+    queue.retain(|m| m.collateral != queue_member.collateral);
+
+    if queue_member.amount == 0 {
+        return;
+    }
+
+    let position = queue.partition_point(|m| m.rate <= queue_member.rate);
+
+    if position >= Oracle::MAX_PRIORITY_QUEUE_LENGTH {
+        return;
+    }
+
+    queue.insert(position, queue_member);
+    queue.truncate(Oracle::MAX_PRIORITY_QUEUE_LENGTH);
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq)]
 pub struct QueueMember {
+    /// Collateral address
     pub collateral: Pubkey,
+    /// User PDA that owns the collateral
+    pub user: Pubkey,
+    /// Rest amount of the collateral that can be liquidated
     pub amount: u64,
+    /// Rate of the collateral owner at the moment of the last update
+    pub rate: u64,
+}
+
+impl QueueMember {
+    pub const SIZE: usize = 32 + 32 + 8 + 8;
 }
 
 #[account]
@@ -95,9 +453,34 @@ pub struct Collateral {
     pub bump: u8,
     /// Flag that indicates the type of stake (can be LP token account or native staking pool)
     pub is_native: bool,
+    /// Epoch through which the storage fee is accrued
+    pub fee_paid_through_epoch: u64,
+    /// Storage fee that is accrued, but not paid yet
+    pub accrued_storage_fee: u64,
+    /// Vote account that the deactivating stake will be redelegated to
+    pub pending_vote_account: Option<Pubkey>,
+    /// Lamports delegated on redelegation above the rest amount, that aren't credited as rewards
+    pub redelegation_excess: u64,
 }
 
 impl Collateral {
+    pub fn get_rest_amount(&self) -> u64 {
+        self.delegation_stake.saturating_sub(self.liquidated_amount)
+    }
+    /// Returns the rewards of the native collateral stake account, that delegates [stake] lamports,
+    /// which aren't synced yet. Liquidated part of the collateral has already been split from the stake account,
+    /// the excess delegated on redelegation isn't collateral's yield.
+    pub fn get_unsynced_rewards(&self, stake: u64) -> u64 {
+        stake
+            .saturating_sub(self.redelegation_excess)
+            .saturating_add(self.liquidated_amount)
+            .saturating_sub(self.delegation_stake)
+    }
+    /// Checks if nothing rests in the pool and the whole [delegation_value] is minted,
+    /// so the collateral can be closed. The value is in lamports, LP tokens are valued by their stake pool.
+    pub fn is_closable(&self, delegation_value: u64) -> bool {
+        self.get_rest_amount() == 0 && self.amount >= delegation_value
+    }
     pub fn get_source_stake(&self) -> Pubkey {
         if self.is_native {
             self.delegated_stake
@@ -105,8 +488,46 @@ impl Collateral {
             self.stake_source
         }
     }
+    /// Accrues the storage fee of the rest amount for the epochs since the last checkpoint.
+    /// Returns the newly accrued fee.
+    pub fn settle_storage_fee(&mut self, storage_fee: u16, epoch: u64) -> u64 {
+        // the fee compounds, so the already accrued fee is not charged again
+        let amount = self.get_rest_amount().saturating_sub(self.accrued_storage_fee);
+        let fee = get_storage_fee(storage_fee, epoch, self.fee_paid_through_epoch, amount);
+
+        self.accrued_storage_fee = self.accrued_storage_fee.saturating_add(fee);
+        self.fee_paid_through_epoch = self.fee_paid_through_epoch.max(epoch);
+
+        fee
+    }
+    /// Takes the part of the accrued storage fee that falls on the withdrawn [amount] (rounded up).
+    pub fn take_storage_fee(&mut self, amount: u64) -> u64 {
+        let rest_amount = self.get_rest_amount();
+
+        let fee = if amount >= rest_amount {
+            self.accrued_storage_fee
+        } else {
+            let fee =
+                (self.accrued_storage_fee as u128 * amount as u128 + rest_amount as u128 - 1) / rest_amount as u128;
+            (fee as u64).min(self.accrued_storage_fee)
+        };
+
+        self.accrued_storage_fee -= fee;
+
+        fee
+    }
+    /// Withdraws [amount] of stake from the collateral, fees included, as they leave the collateral stake too.
+    /// Returns the amount of omniSOL to burn.
+    pub fn withdraw(&mut self, amount: u64, with_burn: bool) -> u64 {
+        let burn_amount = if with_burn { amount.min(self.amount) } else { 0 };
+
+        self.delegation_stake -= amount;
+        self.amount -= burn_amount;
+
+        burn_amount
+    }
     pub const SEED: &'static [u8] = b"collateral";
-    pub const SIZE: usize = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1;
+    pub const SIZE: usize = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 8 + 8 + 33 + 8;
 }
 
 #[account]
@@ -128,15 +549,62 @@ impl Whitelist {
 pub struct WithdrawInfo {
     /// User that made withdraw request
     pub authority: Pubkey,
+    /// Pool of the user account that made withdraw request
+    pub pool: Pubkey,
     /// Amount of omnisol burnt
     pub amount: u64,
     /// Time of withdraw request creation
     pub created_at: i64,
+    /// Way of fulfilling the request
+    pub mode: WithdrawMode,
+    /// Deactivating stake account that the delayed request is claimed from (default until it's deactivated)
+    pub stake_account: Pubkey,
+    /// Epoch since which the delayed request can be claimed
+    pub claimable_epoch: u64,
+    /// Wallet of the collateral owner, that gets the lamports of the deactivated stake above the request amount
+    pub collateral_owner_wallet: Pubkey,
+    /// Minimal amount of lamports the user has to receive for the rest amount of the request
+    pub min_lamports_out: u64,
+    /// Liquidation fee charged for the rest amount of the request, escrowed until it's fulfilled
+    pub fee: u64,
+    /// Way the liquidation fee was paid: escrowed in the pool treasury in SOL or burned along with the request
+    pub fee_mode: FeeMode,
 }
 
 impl WithdrawInfo {
     pub const SEED: &'static [u8] = b"withdraw_info";
-    pub const SIZE: usize = 8 + 32 + 8 + 8;
+    pub const SIZE: usize = 8 + 32 + 32 + 8 + 8 + 1 + 32 + 8 + 32 + 8 + 8 + 1;
+    /// Returns the part of the minimal lamports out that falls on the fulfilled [amount] (rounded up).
+    pub fn get_min_lamports_out(&self, amount: u64) -> u64 {
+        self.get_part(self.min_lamports_out, amount)
+    }
+    /// Returns the part of the liquidation fee that falls on the fulfilled [amount] (rounded up).
+    pub fn get_fee(&self, amount: u64) -> u64 {
+        self.get_part(self.fee, amount)
+    }
+    fn get_part(&self, value: u64, amount: u64) -> u64 {
+        if self.amount == 0 || amount >= self.amount {
+            return value;
+        }
+
+        let part = (value as u128 * amount as u128 + self.amount as u128 - 1) / self.amount as u128;
+
+        (part as u64).min(value)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WithdrawMode {
+    /// The liquidator instantly unstakes collateral and sends SOL to the user
+    Instant,
+    /// The liquidator deactivates collateral stake and the user claims SOL after the cooldown
+    Delayed,
+}
+
+impl Default for WithdrawMode {
+    fn default() -> Self {
+        Self::Instant
+    }
 }
 
 #[account]
@@ -154,17 +622,46 @@ impl Liquidator {
 pub struct Manager {
     /// Manager wallet address
     pub manager: Pubkey,
+    /// Bitmask of actions that the manager is allowed to do
+    pub permissions: u8,
 }
 
 impl Manager {
     pub const SEED: &'static [u8] = b"manager";
-    pub const SIZE: usize = 8 + 32;
+    pub const SIZE: usize = 8 + 32 + 1;
+
+    /// Update pool fees, fee receiver and minimal deposit
+    pub const FEES: u8 = 1 << 0;
+    /// Pause and resume pools
+    pub const PAUSE: u8 = 1 << 1;
+    /// Add and remove whitelisted tokens
+    pub const WHITELIST: u8 = 1 << 2;
+    /// Block and unblock users
+    pub const BLOCK: u8 = 1 << 3;
+    /// Sweep SOL fees from pool treasuries
+    pub const TREASURY: u8 = 1 << 4;
+    /// Deactivate and redelegate collateral stake
+    pub const STAKE: u8 = 1 << 5;
+    /// Initialize new pools
+    pub const POOLS: u8 = 1 << 6;
+    pub const ALL_PERMISSIONS: u8 =
+        Self::FEES | Self::PAUSE | Self::WHITELIST | Self::BLOCK | Self::TREASURY | Self::STAKE | Self::POOLS;
+
+    pub fn has_permission(&self, permission: u8) -> bool {
+        self.permissions & permission == permission
+    }
+
+    pub fn is_valid_permissions(permissions: u8) -> bool {
+        permissions & !Self::ALL_PERMISSIONS == 0
+    }
 }
 
 #[account]
 pub struct User {
     /// Wallet of registered user
     pub wallet: Pubkey,
+    /// Pool the user account belongs to
+    pub pool: Pubkey,
     /// Rate value for priority queue
     pub rate: u64,
     /// Flag that indicates that the user is blocked or not
@@ -177,7 +674,7 @@ pub struct User {
 
 impl User {
     pub const SEED: &'static [u8] = b"user";
-    pub const SIZE: usize = 8 + 32 + 8 + 1 + 4 + 4;
+    pub const SIZE: usize = 8 + 32 + 32 + 8 + 1 + 4 + 4;
     pub fn get_index(&self) -> u32 {
         self.last_withdraw_index - self.requests_amount + 1
     }
@@ -185,3 +682,644 @@ impl User {
         self.last_withdraw_index + 1
     }
 }
+
+/// Global user account that was used before users became per-pool.
+/// It has the same discriminator as [User] and is seeded only by the wallet.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyUser {
+    pub wallet: Pubkey,
+    pub rate: u64,
+    pub is_blocked: bool,
+    pub requests_amount: u32,
+    pub last_withdraw_index: u32,
+}
+
+impl LegacyUser {
+    pub const SIZE: usize = 8 + 32 + 8 + 1 + 4 + 4;
+    pub fn get_index(&self) -> u32 {
+        self.last_withdraw_index - self.requests_amount + 1
+    }
+}
+
+/// Withdraw request of the global user account. It has the same discriminator as [WithdrawInfo]
+/// and is seeded by the wallet instead of the user account.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyWithdrawInfo {
+    pub authority: Pubkey,
+    pub amount: u64,
+    pub created_at: i64,
+}
+
+impl LegacyWithdrawInfo {
+    pub const SIZE: usize = 8 + 32 + 8 + 8;
+}
+
+/// Manager account that was used before managers got permissions.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyManager {
+    pub manager: Pubkey,
+}
+
+impl LegacyManager {
+    pub const SIZE: usize = 8 + 32;
+}
+
+/// Pool account that was used before minimal mint and burn amounts, fee modes, fee recipients,
+/// referrals and validator policies were added. Its fees are set in 1/1000.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyPool {
+    pub pool_mint: Pubkey,
+    pub authority: Pubkey,
+    pub stake_source: Pubkey,
+    pub deposit_amount: u64,
+    pub collaterals_amount: u64,
+    pub authority_bump: u8,
+    pub is_active: bool,
+    pub fee_receiver: Pubkey,
+    pub withdraw_fee: u16,
+    pub mint_fee: u16,
+    pub deposit_fee: u16,
+    pub storage_fee: u16,
+    pub min_deposit: u64,
+}
+
+impl LegacyPool {
+    pub const SIZE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1 + 1 + 32 + 2 + 2 + 2 + 2 + 8;
+}
+
+/// Converts the legacy fee in 1/1000 to basis points.
+pub fn legacy_fee_to_bps(fee: u16) -> Option<u16> {
+    fee.checked_mul(10)
+}
+
+/// Collateral account that was used before the storage fee was settled per epoch
+/// and the stake could be redelegated.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyCollateral {
+    pub user: Pubkey,
+    pub pool: Pubkey,
+    pub stake_source: Pubkey,
+    pub delegated_stake: Pubkey,
+    pub delegation_stake: u64,
+    pub amount: u64,
+    pub liquidated_amount: u64,
+    pub created_at: i64,
+    pub creation_epoch: u64,
+    pub bump: u8,
+    pub is_native: bool,
+}
+
+impl LegacyCollateral {
+    pub const SIZE: usize = 8 + 32 + 32 + 32 + 32 + 8 + 8 + 8 + 8 + 8 + 1 + 1;
+}
+
+impl From<LegacyCollateral> for Collateral {
+    fn from(legacy_collateral: LegacyCollateral) -> Self {
+        Self {
+            user: legacy_collateral.user,
+            pool: legacy_collateral.pool,
+            stake_source: legacy_collateral.stake_source,
+            delegated_stake: legacy_collateral.delegated_stake,
+            delegation_stake: legacy_collateral.delegation_stake,
+            amount: legacy_collateral.amount,
+            liquidated_amount: legacy_collateral.liquidated_amount,
+            created_at: legacy_collateral.created_at,
+            creation_epoch: legacy_collateral.creation_epoch,
+            bump: legacy_collateral.bump,
+            is_native: legacy_collateral.is_native,
+            // the storage fee was charged since the creation
+            fee_paid_through_epoch: legacy_collateral.creation_epoch,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
+        }
+    }
+}
+
+/// Liquidation fee account that was used before fee recipients were added.
+/// Its fee is set in 1/1000.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyLiquidationFee {
+    pub fee_receiver: Pubkey,
+    pub fee: u16,
+}
+
+impl LegacyLiquidationFee {
+    pub const SIZE: usize = 8 + 32 + 2;
+}
+
+/// Oracle account that was used before the queue members got users and rates and the staging queue was added.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyOracle {
+    pub authority: Pubkey,
+    pub priority_queue: Vec<LegacyQueueMember>,
+}
+
+impl LegacyOracle {
+    pub const SIZE: usize = 4068;
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyQueueMember {
+    pub collateral: Pubkey,
+    pub amount: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue_member(collateral: Pubkey, user: Pubkey, amount: u64, rate: u64) -> QueueMember {
+        QueueMember {
+            collateral,
+            user,
+            amount,
+            rate,
+        }
+    }
+
+    #[test]
+    fn test_priority_queue_maintenance() {
+        let mut oracle = Oracle {
+            authority: Pubkey::new_unique(),
+            priority_queue: vec![],
+            staging_queue: vec![],
+        };
+        let (user_1, user_2) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (collateral_1, collateral_2, collateral_3) =
+            (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+
+        oracle.upsert(queue_member(collateral_1, user_1, 100, 100));
+        oracle.upsert(queue_member(collateral_2, user_2, 50, 50));
+        oracle.upsert(queue_member(collateral_3, user_1, 10, 110));
+        oracle.set_rate(user_1, 110);
+
+        let order: Vec<_> = oracle.priority_queue.iter().map(|m| m.collateral).collect();
+        assert_eq!(order, vec![collateral_2, collateral_1, collateral_3]);
+
+        // user_1 minted omniSOL, so their collaterals are liquidated first
+        oracle.set_rate(user_1, 10);
+        let order: Vec<_> = oracle.priority_queue.iter().map(|m| m.collateral).collect();
+        assert_eq!(order, vec![collateral_1, collateral_3, collateral_2]);
+
+        // upsert replaces the existing member
+        oracle.upsert(queue_member(collateral_2, user_2, 40, 0));
+        assert_eq!(oracle.priority_queue.len(), 3);
+        assert_eq!(oracle.priority_queue[0], queue_member(collateral_2, user_2, 40, 0));
+
+        // zero amount removes the member
+        oracle.upsert(queue_member(collateral_1, user_1, 0, 10));
+        oracle.remove(&collateral_3);
+        assert_eq!(oracle.priority_queue, vec![queue_member(collateral_2, user_2, 40, 0)]);
+        assert_eq!(oracle.staging_queue, oracle.priority_queue);
+    }
+
+    #[test]
+    fn test_priority_queue_commit() {
+        let mut oracle = Oracle {
+            authority: Pubkey::new_unique(),
+            priority_queue: vec![],
+            staging_queue: vec![],
+        };
+        let user = Pubkey::new_unique();
+        let (collateral_1, collateral_2) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        oracle.upsert(queue_member(collateral_1, user, 100, 100));
+        oracle.commit();
+        assert_eq!(oracle.priority_queue, vec![queue_member(collateral_1, user, 100, 100)]);
+        assert!(oracle.staging_queue.is_empty());
+
+        // staged members are not visible until commit
+        oracle.stage(queue_member(collateral_2, user, 50, 100));
+        assert_eq!(oracle.priority_queue, vec![queue_member(collateral_1, user, 100, 100)]);
+
+        // user updates reach both queues
+        oracle.set_rate(user, 10);
+        oracle.commit();
+        assert_eq!(oracle.priority_queue, vec![queue_member(collateral_2, user, 50, 10)]);
+        assert!(oracle.staging_queue.is_empty());
+    }
+
+    #[test]
+    fn test_priority_queue_length_limit() {
+        let mut oracle = Oracle {
+            authority: Pubkey::new_unique(),
+            priority_queue: vec![],
+            staging_queue: vec![],
+        };
+
+        for rate in 1..=Oracle::MAX_PRIORITY_QUEUE_LENGTH as u64 {
+            oracle.upsert(queue_member(Pubkey::new_unique(), Pubkey::new_unique(), 1, rate));
+        }
+
+        let collateral = Pubkey::new_unique();
+        oracle.upsert(queue_member(collateral, Pubkey::new_unique(), 1, u64::MAX));
+        assert!(oracle.priority_queue.iter().all(|m| m.collateral != collateral));
+
+        oracle.upsert(queue_member(collateral, Pubkey::new_unique(), 1, 0));
+        assert_eq!(oracle.priority_queue.len(), Oracle::MAX_PRIORITY_QUEUE_LENGTH);
+        assert_eq!(oracle.priority_queue[0].collateral, collateral);
+        assert_eq!(
+            oracle.priority_queue.last().unwrap().rate,
+            Oracle::MAX_PRIORITY_QUEUE_LENGTH as u64 - 1
+        );
+
+        // both full queues fit into the account
+        oracle.staging_queue = oracle.priority_queue.clone();
+        assert!(8 + oracle.try_to_vec().unwrap().len() <= Oracle::SIZE);
+        assert!(Oracle::SIZE - Oracle::INIT_SIZE <= MAX_PERMITTED_DATA_INCREASE);
+    }
+
+    #[test]
+    fn test_storage_fee_settlement() {
+        let mut collateral = Collateral {
+            user: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            stake_source: Pubkey::new_unique(),
+            delegated_stake: Pubkey::new_unique(),
+            delegation_stake: 1_000_000,
+            amount: 0,
+            liquidated_amount: 0,
+            created_at: 0,
+            creation_epoch: 10,
+            bump: 255,
+            is_native: true,
+            fee_paid_through_epoch: 10,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
+        };
+
+        // 1% per epoch for 2 epochs
+        assert_eq!(collateral.settle_storage_fee(100, 12), 19_900);
+        assert_eq!(collateral.fee_paid_through_epoch, 12);
+
+        // settled epochs are not charged twice
+        assert_eq!(collateral.settle_storage_fee(100, 12), 0);
+        assert_eq!(collateral.settle_storage_fee(100, 11), 0);
+        assert_eq!(collateral.fee_paid_through_epoch, 12);
+
+        // withdrawal of a half takes a half of the accrued fee
+        assert_eq!(collateral.take_storage_fee(500_000), 9_950);
+        collateral.delegation_stake -= 500_000;
+        assert_eq!(collateral.accrued_storage_fee, 9_950);
+
+        // the rest is charged only for the new epochs
+        assert_eq!(collateral.settle_storage_fee(100, 13), 4_901);
+        assert_eq!(collateral.take_storage_fee(500_000), 14_851);
+        assert_eq!(collateral.accrued_storage_fee, 0);
+    }
+
+    #[test]
+    fn test_withdrawal() {
+        let mut collateral = Collateral {
+            user: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            stake_source: Pubkey::new_unique(),
+            delegated_stake: Pubkey::new_unique(),
+            delegation_stake: 10_000_000_000,
+            amount: 4_000_000_000,
+            liquidated_amount: 0,
+            created_at: 0,
+            creation_epoch: 0,
+            bump: 255,
+            is_native: true,
+            fee_paid_through_epoch: 0,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
+        };
+
+        // the unminted part is withdrawn without burning
+        assert_eq!(collateral.withdraw(6_000_000_000, false), 0);
+        assert_eq!(collateral.get_rest_amount(), 4_000_000_000);
+        assert_eq!(collateral.amount, 4_000_000_000);
+
+        // the user burns their omniSOL to withdraw the rest, the fee split off it leaves no dust
+        assert_eq!(collateral.withdraw(4_000_000_000, true), 4_000_000_000);
+        assert_eq!(collateral.get_rest_amount(), 0);
+        assert_eq!(collateral.amount, 0);
+        assert!(collateral.is_closable(collateral.delegation_stake));
+    }
+
+    #[test]
+    fn test_lp_collateral_closing() {
+        use spl_stake_pool::state::StakePool;
+
+        use crate::utils::stake_pool::get_lamports_value;
+
+        let stake_pool_at = |total_lamports| StakePool {
+            total_lamports,
+            pool_token_supply: 1_000_000_000,
+            ..StakePool::default()
+        };
+
+        let mut collateral = Collateral {
+            user: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            stake_source: Pubkey::new_unique(),
+            delegated_stake: Pubkey::default(),
+            delegation_stake: 1_000_000_000,
+            amount: 0,
+            liquidated_amount: 0,
+            created_at: 0,
+            creation_epoch: 0,
+            bump: 255,
+            is_native: false,
+            fee_paid_through_epoch: 0,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
+        };
+
+        // the whole collateral is minted at 1.05
+        let stake_pool = stake_pool_at(1_050_000_000);
+        collateral.amount += get_lamports_value(&stake_pool, collateral.delegation_stake).unwrap();
+        assert_eq!(collateral.amount, 1_050_000_000);
+        assert!(!collateral.is_closable(get_lamports_value(&stake_pool, collateral.delegation_stake).unwrap()));
+
+        // and fully withdrawn with burn at 1.1, LP tokens and lamports don't match anymore
+        let stake_pool = stake_pool_at(1_100_000_000);
+        let lamports = get_lamports_value(&stake_pool, collateral.get_rest_amount()).unwrap();
+        collateral.amount -= lamports.min(collateral.amount);
+        collateral.delegation_stake = 0;
+        assert!(collateral.is_closable(get_lamports_value(&stake_pool, collateral.delegation_stake).unwrap()));
+
+        // fully liquidated collateral is closed once its appreciated value is minted
+        collateral.delegation_stake = 1_000_000_000;
+        collateral.liquidated_amount = 1_000_000_000;
+        collateral.amount = 1_050_000_000;
+        let delegation_value = get_lamports_value(&stake_pool, collateral.delegation_stake).unwrap();
+        assert!(!collateral.is_closable(delegation_value));
+        collateral.amount += delegation_value - collateral.amount;
+        assert!(collateral.is_closable(delegation_value));
+    }
+
+    #[test]
+    fn test_validator_policy() {
+        let mut validator = Validator {
+            pool: Pubkey::new_unique(),
+            vote_account: Pubkey::new_unique(),
+            is_allowed: true,
+            max_collateral: 0,
+            collateral_amount: 0,
+            bump: 255,
+        };
+
+        assert!(ValidatorPolicy::Any.allows(None));
+        assert!(!ValidatorPolicy::Allowlist.allows(None));
+        assert!(ValidatorPolicy::Denylist.allows(None));
+        assert!(ValidatorPolicy::Allowlist.allows(Some(&validator)));
+        assert!(ValidatorPolicy::Denylist.allows(Some(&validator)));
+
+        validator.is_allowed = false;
+        assert!(ValidatorPolicy::Any.allows(Some(&validator)));
+        assert!(!ValidatorPolicy::Allowlist.allows(Some(&validator)));
+        assert!(!ValidatorPolicy::Denylist.allows(Some(&validator)));
+    }
+
+    #[test]
+    fn test_unsynced_rewards() {
+        let mut collateral = Collateral {
+            user: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            stake_source: Pubkey::new_unique(),
+            delegated_stake: Pubkey::new_unique(),
+            delegation_stake: 10_000,
+            amount: 0,
+            liquidated_amount: 3_000,
+            created_at: 0,
+            creation_epoch: 0,
+            bump: 0,
+            is_native: true,
+            fee_paid_through_epoch: 0,
+            accrued_storage_fee: 0,
+            pending_vote_account: None,
+            redelegation_excess: 0,
+        };
+
+        // the liquidated part has left the stake account
+        assert_eq!(collateral.get_unsynced_rewards(7_000), 0);
+        assert_eq!(collateral.get_unsynced_rewards(7_500), 500);
+        assert_eq!(collateral.get_unsynced_rewards(6_000), 0);
+
+        // the redelegation excess isn't synced
+        collateral.redelegation_excess = 1_000;
+        assert_eq!(collateral.get_unsynced_rewards(8_000), 0);
+        assert_eq!(collateral.get_unsynced_rewards(8_200), 200);
+    }
+
+    #[test]
+    fn test_validator_capacity() {
+        let mut validator = Validator {
+            pool: Pubkey::new_unique(),
+            vote_account: Pubkey::new_unique(),
+            is_allowed: true,
+            max_collateral: 0,
+            collateral_amount: 1_000,
+            bump: 255,
+        };
+
+        // zero cap is unlimited
+        assert!(validator.has_capacity(u64::MAX));
+
+        validator.max_collateral = 1_500;
+        assert!(validator.has_capacity(500));
+        assert!(!validator.has_capacity(501));
+        assert!(!validator.has_capacity(u64::MAX));
+    }
+
+    #[test]
+    fn test_min_lamports_out() {
+        let mut withdraw_info = WithdrawInfo {
+            authority: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            amount: 1_000,
+            created_at: 0,
+            mode: WithdrawMode::Instant,
+            stake_account: Pubkey::default(),
+            claimable_epoch: 0,
+            collateral_owner_wallet: Pubkey::default(),
+            min_lamports_out: 990,
+            fee: 10,
+            fee_mode: FeeMode::Sol,
+        };
+        assert_eq!(8 + withdraw_info.try_to_vec().unwrap().len(), WithdrawInfo::SIZE);
+
+        assert_eq!(withdraw_info.get_min_lamports_out(1_000), 990);
+        assert_eq!(withdraw_info.get_min_lamports_out(500), 495);
+        assert_eq!(withdraw_info.get_min_lamports_out(1), 1); // rounded up
+
+        // the rest of the request keeps the proportion
+        withdraw_info.min_lamports_out -= withdraw_info.get_min_lamports_out(300);
+        withdraw_info.amount -= 300;
+        assert_eq!(withdraw_info.min_lamports_out, 693);
+        assert_eq!(withdraw_info.get_min_lamports_out(700), 693);
+
+        withdraw_info.min_lamports_out = 0;
+        assert_eq!(withdraw_info.get_min_lamports_out(500), 0);
+    }
+
+    #[test]
+    fn test_withdraw_fee_refund() {
+        let mut withdraw_info = WithdrawInfo {
+            authority: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            amount: 1_000,
+            created_at: 0,
+            mode: WithdrawMode::Instant,
+            stake_account: Pubkey::default(),
+            claimable_epoch: 0,
+            collateral_owner_wallet: Pubkey::default(),
+            min_lamports_out: 0,
+            fee: 10,
+            fee_mode: FeeMode::Sol,
+        };
+
+        // the fee of the fulfilled part is rounded up, so the refund of the rest is rounded down
+        assert_eq!(withdraw_info.get_fee(1), 1);
+        withdraw_info.fee -= withdraw_info.get_fee(150);
+        withdraw_info.amount -= 150;
+        assert_eq!(withdraw_info.fee, 8);
+
+        assert_eq!(withdraw_info.get_fee(withdraw_info.amount), 8);
+        assert_eq!(withdraw_info.get_fee(u64::MAX), 8);
+    }
+
+    #[test]
+    fn test_liquidation_fee_escrow() {
+        let mut treasury = Treasury {
+            pool: Pubkey::new_unique(),
+            deposit_fees: 0,
+            mint_fees: 0,
+            withdraw_fees: 0,
+            storage_fees: 0,
+            liquidation_fees: 0,
+            swept_amount: 0,
+            bump: 255,
+            liquidation_fee_balance: 0,
+            in_kind_liquidation_fee_balance: 0,
+            escrowed_liquidation_fees: 10,
+        };
+
+        // the fee of the fulfilled part can be swept, the rest stays in the escrow for the refund
+        treasury.release_liquidation_fee(FeeMode::Sol, 2);
+        assert_eq!(treasury.escrowed_liquidation_fees, 8);
+        assert_eq!(treasury.liquidation_fee_balance, 2);
+
+        treasury.release_liquidation_fee(FeeMode::InKind, 5);
+        assert_eq!(treasury.escrowed_liquidation_fees, 8);
+        assert_eq!(treasury.in_kind_liquidation_fee_balance, 5);
+    }
+
+    #[test]
+    fn test_legacy_fee_migration() {
+        let legacy_pool = LegacyPool {
+            pool_mint: Pubkey::new_unique(),
+            authority: Pubkey::new_unique(),
+            stake_source: Pubkey::new_unique(),
+            deposit_amount: 0,
+            collaterals_amount: 0,
+            authority_bump: 255,
+            is_active: true,
+            fee_receiver: Pubkey::new_unique(),
+            withdraw_fee: 5,
+            mint_fee: 0,
+            deposit_fee: 1,
+            storage_fee: 1000,
+            min_deposit: 0,
+        };
+        assert_eq!(8 + legacy_pool.try_to_vec().unwrap().len(), LegacyPool::SIZE);
+
+        let legacy_liquidation_fee = LegacyLiquidationFee {
+            fee_receiver: Pubkey::new_unique(),
+            fee: 5,
+        };
+        assert_eq!(
+            8 + legacy_liquidation_fee.try_to_vec().unwrap().len(),
+            LegacyLiquidationFee::SIZE
+        );
+
+        // 0.5% in 1/1000 is 50 basis points
+        assert_eq!(legacy_fee_to_bps(legacy_pool.withdraw_fee), Some(50));
+        assert_eq!(legacy_fee_to_bps(legacy_pool.storage_fee), Some(10_000));
+        assert_eq!(legacy_fee_to_bps(0), Some(0));
+        assert_eq!(legacy_fee_to_bps(u16::MAX), None);
+    }
+
+    #[test]
+    fn test_legacy_oracle_migration() {
+        let legacy_oracle = LegacyOracle {
+            authority: Pubkey::new_unique(),
+            priority_queue: vec![
+                LegacyQueueMember {
+                    collateral: Pubkey::new_unique(),
+                    amount: 1_000,
+                };
+                Oracle::MAX_PRIORITY_QUEUE_LENGTH
+            ],
+        };
+        // the full legacy queue fits into the legacy account
+        assert!(8 + legacy_oracle.try_to_vec().unwrap().len() <= LegacyOracle::SIZE);
+
+        // the migrated account grows within a single realloc, the rest is reallocated by `update_oracle_info`
+        assert!(Oracle::INIT_SIZE - LegacyOracle::SIZE <= MAX_PERMITTED_DATA_INCREASE);
+    }
+
+    #[test]
+    fn test_manager_permissions() {
+        let manager = Manager {
+            manager: Pubkey::new_unique(),
+            permissions: Manager::FEES | Manager::STAKE,
+        };
+
+        assert!(manager.has_permission(Manager::FEES));
+        assert!(manager.has_permission(Manager::FEES | Manager::STAKE));
+        assert!(!manager.has_permission(Manager::POOLS));
+        assert!(!manager.has_permission(Manager::FEES | Manager::POOLS));
+
+        let manager = Manager {
+            permissions: Manager::ALL_PERMISSIONS,
+            ..manager
+        };
+        assert!(manager.has_permission(Manager::POOLS));
+
+        assert!(Manager::is_valid_permissions(Manager::ALL_PERMISSIONS));
+        assert!(!Manager::is_valid_permissions(1 << 7));
+    }
+
+    #[test]
+    fn test_legacy_collateral_migration() {
+        let legacy_collateral = LegacyCollateral {
+            user: Pubkey::new_unique(),
+            pool: Pubkey::new_unique(),
+            stake_source: Pubkey::new_unique(),
+            delegated_stake: Pubkey::new_unique(),
+            delegation_stake: 1_000,
+            amount: 500,
+            liquidated_amount: 100,
+            created_at: 0,
+            creation_epoch: 42,
+            bump: 255,
+            is_native: true,
+        };
+        assert_eq!(
+            8 + legacy_collateral.try_to_vec().unwrap().len(),
+            LegacyCollateral::SIZE
+        );
+
+        let collateral = Collateral::from(legacy_collateral);
+        assert_eq!(collateral.get_rest_amount(), 900);
+        assert_eq!(collateral.fee_paid_through_epoch, 42);
+        assert_eq!(collateral.accrued_storage_fee, 0);
+        assert_eq!(collateral.pending_vote_account, None);
+        assert_eq!(collateral.redelegation_excess, 0);
+
+        // the size fits the redelegating collateral
+        let collateral = Collateral {
+            pending_vote_account: Some(Pubkey::new_unique()),
+            ..collateral
+        };
+        assert_eq!(8 + collateral.try_to_vec().unwrap().len(), Collateral::SIZE);
+    }
+}
@@ -0,0 +1,67 @@
+use anchor_lang::prelude::*;
+
+use crate::{
+    events::SyncCollateralEvent,
+    state::{Collateral, Oracle, Pool, QueueMember, User},
+    utils::validator::load_validator,
+    ErrorCode,
+};
+
+/// Adds the rewards of the native collateral stake account, that delegates [stake] lamports,
+/// to the collateral, the pool deposit amount, the user rate and the collateral amount of the [validator] entry,
+/// that may be uninitialized. Rewards are added to the validator entry even above its cap.
+/// Returns the synced rewards.
+pub fn sync_rewards(
+    pool: &mut Account<Pool>,
+    user: &mut Account<User>,
+    collateral: &mut Account<Collateral>,
+    oracle: &mut Oracle,
+    validator: &AccountInfo,
+    stake: u64,
+    clock: &Clock,
+) -> Result<u64> {
+    let rewards = collateral.get_unsynced_rewards(stake);
+
+    if rewards == 0 {
+        msg!("No rewards to sync");
+        return Ok(0);
+    }
+
+    // rewards are not charged for the epochs before the sync
+    collateral.settle_storage_fee(pool.storage_fee, clock.epoch);
+
+    collateral.delegation_stake = collateral
+        .delegation_stake
+        .checked_add(rewards)
+        .ok_or(ErrorCode::TypeOverflow)?;
+
+    pool.deposit_amount = pool
+        .deposit_amount
+        .checked_add(rewards)
+        .ok_or(ErrorCode::TypeOverflow)?;
+
+    user.rate = user.rate.checked_add(rewards).ok_or(ErrorCode::TypeOverflow)?;
+
+    if let Some(mut validator) = load_validator(validator)? {
+        validator.collateral_amount = validator.collateral_amount.saturating_add(rewards);
+        validator.exit(&crate::ID)?;
+    }
+
+    oracle.set_rate(user.key(), user.rate);
+    oracle.upsert(QueueMember {
+        collateral: collateral.key(),
+        user: user.key(),
+        amount: collateral.get_rest_amount(),
+        rate: user.rate,
+    });
+
+    emit!(SyncCollateralEvent {
+        pool: pool.key(),
+        collateral: collateral.key(),
+        rewards,
+        delegation_stake: collateral.delegation_stake,
+        timestamp: clock.unix_timestamp,
+    });
+
+    Ok(rewards)
+}
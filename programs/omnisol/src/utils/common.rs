@@ -4,7 +4,12 @@ use std::{
 };
 
 use anchor_lang::{
-    error, error::ErrorCode, solana_program::account_info::AccountInfo, Result, __private::CLOSED_ACCOUNT_DISCRIMINATOR,
+    error,
+    error::ErrorCode,
+    prelude::*,
+    solana_program::account_info::AccountInfo,
+    system_program::{self, Transfer},
+    Result, __private::CLOSED_ACCOUNT_DISCRIMINATOR,
 };
 
 pub fn close<'info>(info: AccountInfo<'info>, sol_destination: AccountInfo<'info>) -> Result<()> {
@@ -26,3 +31,56 @@ pub fn close<'info>(info: AccountInfo<'info>, sol_destination: AccountInfo<'info
         .write_all(&CLOSED_ACCOUNT_DISCRIMINATOR)
         .map_err(|_| error!(ErrorCode::AccountDidNotSerialize))
 }
+
+/// Moves lamports from the account owned by the program directly
+pub fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, amount: u64) -> Result<()> {
+    let to_lamports = to
+        .lamports()
+        .checked_add(amount)
+        .ok_or(crate::ErrorCode::TypeOverflow)?;
+
+    **from.try_borrow_mut_lamports()? -= amount;
+    **to.try_borrow_mut_lamports()? = to_lamports;
+
+    Ok(())
+}
+
+/// Reads the account of the previous layout, that has the same discriminator as the current one
+pub fn load_legacy<T: AnchorDeserialize>(info: &AccountInfo, size: usize, discriminator: [u8; 8]) -> Result<T> {
+    if *info.owner != crate::ID {
+        return Err(crate::ErrorCode::WrongData.into());
+    }
+
+    let data = info.try_borrow_data()?;
+    if data.len() != size || data[..8] != discriminator {
+        return Err(crate::ErrorCode::WrongData.into());
+    }
+
+    Ok(T::deserialize(&mut &data[8..])?)
+}
+
+/// Grows the account owned by the program to [new_size], the payer tops up its rent exemption
+pub fn realloc<'info>(
+    info: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+    new_size: usize,
+) -> Result<()> {
+    let lamports = Rent::get()?.minimum_balance(new_size).saturating_sub(info.lamports());
+    if lamports > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                Transfer {
+                    from: payer.clone(),
+                    to: info.clone(),
+                },
+            ),
+            lamports,
+        )?;
+    }
+
+    info.realloc(new_size, false)?;
+
+    Ok(())
+}
@@ -1,23 +1,208 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, system_program};
 
-use crate::ErrorCode;
+use crate::{
+    events::{FeeAccrualEvent, ReferralFeeEvent},
+    state::{FeeMode, FeeRecipient, FeeType, Referral, Treasury},
+    utils::transfer_lamports,
+    ErrorCode,
+};
 
-pub fn get_storage_fee(storage_fee: u64, epoch: u64, creation_epoch: u64, amount: u64) -> u64 {
+/// All fees are set in basis points (1/10000)
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+/// Returns [fee_bps] basis points of the [amount].
+/// The fee is rounded up, so any non-zero fee of a non-zero amount is at least 1 lamport.
+pub fn calc_fee(amount: u64, fee_bps: u16) -> u64 {
+    let denominator = BPS_DENOMINATOR as u128;
+    let fee = (amount as u128 * fee_bps as u128 + denominator - 1) / denominator;
+
+    // fee can't be greater than the amount even with invalid fee value
+    fee.min(amount as u128) as u64
+}
+
+/// Precision of the fixed-point decay factor
+const DECAY_PRECISION: u128 = 1_000_000_000_000_000_000;
+
+/// Returns storage fee of the [amount] stored since [creation_epoch].
+/// The fee compounds: each epoch it is charged from the amount left after the previous epochs,
+/// so the amount left is `amount * (1 - storage_fee)^epochs`.
+/// The power is calculated by squaring in O(log epochs), the fee is rounded up.
+/// Compared to charging the rounded up fee epoch by epoch, the result differs by at most 1 lamport per epoch
+/// for any amount up to the total SOL supply.
+pub fn get_storage_fee(storage_fee: u16, epoch: u64, creation_epoch: u64, amount: u64) -> u64 {
     let stored_in_epochs = epoch.saturating_sub(creation_epoch);
-    let mut delegation = amount;
-    let mut fee: u64 = 0;
 
-    for _ in 0..stored_in_epochs {
-        let current_epoch_fee = delegation.saturating_div(1000).saturating_mul(storage_fee);
-        fee = fee.saturating_add(current_epoch_fee);
-        delegation = delegation.saturating_sub(current_epoch_fee);
+    if stored_in_epochs == 0 || storage_fee == 0 {
+        return 0;
+    }
+
+    let fee_bps = (storage_fee as u64).min(BPS_DENOMINATOR);
+    let decay = (BPS_DENOMINATOR - fee_bps) as u128 * DECAY_PRECISION / BPS_DENOMINATOR as u128;
+
+    // both factors are not greater than the precision, so the product fits into u128
+    let mul = |a: u128, b: u128| a * b / DECAY_PRECISION;
+
+    let mut result = DECAY_PRECISION;
+    let mut base = decay;
+    let mut exponent = stored_in_epochs;
+
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exponent >>= 1;
+    }
+
+    let rest_amount = amount as u128 * result / DECAY_PRECISION;
+
+    amount - rest_amount as u64
+}
+
+/// Records collected fee in the pool treasury.
+pub fn record_fee(treasury: &mut Treasury, fee_type: FeeType, fee_mode: FeeMode, amount: u64, timestamp: i64) {
+    if amount == 0 {
+        return;
+    }
+
+    treasury.accrue(fee_type, amount);
+
+    emit!(FeeAccrualEvent {
+        pool: treasury.pool,
+        fee_type,
+        fee_mode,
+        amount,
+        timestamp,
+    });
+}
+
+/// Returns the shares of the [amount] for each recipient (rounded down).
+/// The rest of the amount goes to the main fee receiver.
+pub fn split_fee(amount: u64, recipients: &[FeeRecipient]) -> Vec<u64> {
+    recipients
+        .iter()
+        .map(|recipient| (amount as u128 * recipient.share as u128 / BPS_DENOMINATOR as u128) as u64)
+        .collect()
+}
+
+/// Returns whether a wallet with [balance] lamports can receive the [amount],
+/// a new wallet has to get at least the rent-exempt minimum.
+pub fn is_payable(rent: &Rent, balance: u64, data_len: usize, amount: u64) -> bool {
+    amount > 0 && rent.is_exempt(balance.saturating_add(amount), data_len)
+}
+
+/// Pays the shares of the [amount] from the [from] account to the fee [recipients],
+/// whose wallets have to be passed as [recipient_accounts] in the same order.
+/// Shares that can't be paid to a wallet without the rent-exempt minimum are skipped.
+/// Returns the rest of the amount, that goes to the main fee receiver.
+pub fn pay_fee_recipients(
+    from: &AccountInfo,
+    recipients: &[FeeRecipient],
+    recipient_accounts: &[AccountInfo],
+    amount: u64,
+) -> Result<u64> {
+    if recipient_accounts.len() < recipients.len() {
+        msg!("Expected {} fee recipients", recipients.len());
+        return Err(ErrorCode::WrongData.into());
+    }
+
+    let rent = Rent::get()?;
+    let shares = split_fee(amount, recipients);
+    let mut rest_amount = amount;
+
+    for ((fee_recipient, share), recipient) in recipients.iter().zip(shares).zip(recipient_accounts) {
+        if recipient.key() != fee_recipient.wallet || !recipient.is_writable {
+            return Err(ErrorCode::InvalidFeeReceiver.into());
+        }
+
+        if !is_payable(&rent, recipient.lamports(), recipient.data_len(), share) {
+            msg!("Skip {} lamports of fee recipient {}", share, recipient.key());
+            continue;
+        }
+
+        transfer_lamports(from, recipient, share)?;
+        rest_amount -= share;
+    }
+
+    Ok(rest_amount)
+}
+
+/// Pays the referrer's share of the SOL [fee], if the referral and the referrer wallet accounts are passed.
+/// The user can't refer themselves, so the referrer can't be the [user_wallet] or the fee payer.
+/// A share that can't be paid to a referrer wallet without the rent-exempt minimum is skipped.
+/// Returns the paid share.
+#[allow(clippy::too_many_arguments)]
+pub fn pay_referral_fee<'info>(
+    referral_accounts: &[AccountInfo<'info>],
+    pool: Pubkey,
+    referral_share: u16,
+    user_wallet: Pubkey,
+    fee_payer: AccountInfo<'info>,
+    system_program: AccountInfo<'info>,
+    fee: u64,
+    timestamp: i64,
+) -> Result<u64> {
+    let (referral_info, referrer) = match referral_accounts {
+        [referral_info, referrer, ..] => (referral_info, referrer),
+        _ => return Ok(0),
+    };
+
+    let mut referral = Account::<Referral>::try_from(referral_info)?;
+    if referral.pool != pool || referral.referrer != referrer.key() || !referrer.is_writable {
+        return Err(ErrorCode::InvalidReferrer.into());
+    }
+
+    if referrer.key() == user_wallet || referrer.key() == fee_payer.key() {
+        msg!("Self-referral is not allowed");
+        return Err(ErrorCode::InvalidReferrer.into());
+    }
+
+    let amount = (fee as u128 * referral_share as u128 / BPS_DENOMINATOR as u128) as u64;
+    if !is_payable(&Rent::get()?, referrer.lamports(), referrer.data_len(), amount) {
+        return Ok(0);
+    }
+
+    msg!("Transfer referral fee: {} lamports", amount);
+
+    system_program::transfer(
+        CpiContext::new(
+            system_program,
+            system_program::Transfer {
+                from: fee_payer,
+                to: referrer.to_account_info(),
+            },
+        ),
+        amount,
+    )
+    .map_err(|_| ErrorCode::InsufficientFunds)?;
+
+    referral.total_fees = referral.total_fees.saturating_add(amount);
+    referral.exit(&crate::ID)?;
+
+    emit!(ReferralFeeEvent {
+        pool,
+        referrer: referrer.key(),
+        amount,
+        total_fees: referral.total_fees,
+        timestamp,
+    });
+
+    Ok(amount)
+}
+
+pub fn check_fee_recipients(recipients: &[FeeRecipient]) -> Result<()> {
+    let total_share: u64 = recipients.iter().map(|recipient| recipient.share as u64).sum();
+
+    if recipients.len() > FeeRecipient::MAX_RECIPIENTS || total_share > BPS_DENOMINATOR {
+        msg!("Invalid fee recipients");
+        return Err(ErrorCode::WrongData.into());
     }
 
-    fee
+    Ok(())
 }
 
 pub fn check_fee(fee: u16) -> Result<()> {
-    if fee > 1000 {
+    if fee as u64 > BPS_DENOMINATOR {
         msg!("Invalid fee value");
         return Err(ErrorCode::WrongData.into());
     }
@@ -29,22 +214,170 @@ pub fn check_fee(fee: u16) -> Result<()> {
 mod tests {
     use super::*;
 
+    /// Deterministic pseudo-random values for property checks
+    fn samples(count: usize) -> impl Iterator<Item = u64> {
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        (0..count).map(move |_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        })
+    }
+
+    #[test]
+    fn test_calc_fee() {
+        assert_eq!(calc_fee(1_000_000_000, 100), 10_000_000); // 1% of 1 SOL
+        assert_eq!(calc_fee(999, 10), 1); // rounded up instead of zero
+        assert_eq!(calc_fee(10_000, 1), 1);
+        assert_eq!(calc_fee(10_001, 1), 2);
+        assert_eq!(calc_fee(0, 100), 0);
+        assert_eq!(calc_fee(100, 0), 0);
+        assert_eq!(calc_fee(u64::MAX, 10_000), u64::MAX);
+        assert_eq!(calc_fee(100, u16::MAX), 100);
+    }
+
     #[test]
-    fn test_generate_priority_queue() {
+    fn test_calc_fee_properties() {
+        for (amount, fee_bps) in samples(10_000).zip(samples(10_000).skip(1)) {
+            // check both full range and small amounts
+            for amount in [amount, amount % 100_000] {
+                let fee_bps = (fee_bps % (BPS_DENOMINATOR + 1)) as u16;
+                let fee = calc_fee(amount, fee_bps);
+                let exact = amount as u128 * fee_bps as u128;
+
+                // never less than the exact value and less than 1 lamport above it
+                assert!(fee as u128 * BPS_DENOMINATOR as u128 >= exact);
+                assert!((fee as u128) * (BPS_DENOMINATOR as u128) < exact + BPS_DENOMINATOR as u128);
+                assert!(fee <= amount);
+                assert_eq!(fee == 0, amount == 0 || fee_bps == 0);
+
+                // monotonic by amount and fee
+                assert!(calc_fee(amount.saturating_add(1), fee_bps) >= fee);
+                assert!(calc_fee(amount, (fee_bps + 1).min(BPS_DENOMINATOR as u16)) >= fee);
+            }
+        }
+    }
+
+    /// Charges the storage fee epoch by epoch
+    fn get_iterative_storage_fee(storage_fee: u16, epoch: u64, creation_epoch: u64, amount: u64) -> u64 {
+        let stored_in_epochs = epoch.saturating_sub(creation_epoch);
+        let mut delegation = amount;
+        let mut fee: u64 = 0;
+
+        for _ in 0..stored_in_epochs {
+            let current_epoch_fee = calc_fee(delegation, storage_fee);
+            fee = fee.saturating_add(current_epoch_fee);
+            delegation = delegation.saturating_sub(current_epoch_fee);
+        }
+
+        fee
+    }
+
+    #[test]
+    fn test_get_storage_fee() {
         // Input params
-        let storage_fee = 10; // 1%
+        let storage_fee = 100; // 1%
         let epoch = 3;
         let creation_epoch = 1;
         let amount = 1000000000; // 1 SOL
 
         // First iteration
-        let fee1 = amount / 1000 * storage_fee; // 1% of 1 SOL
+        let fee1 = amount / 100; // 1% of 1 SOL
 
         // Second iteration
-        let fee2 = (amount - fee1) / 1000 * storage_fee; // 1% of 0.99 SOL
+        let fee2 = (amount - fee1) / 100; // 1% of 0.99 SOL
 
         let result = fee1 + fee2; // sum of all iterations' fees
 
         assert_eq!(get_storage_fee(storage_fee, epoch, creation_epoch, amount), result);
+        assert_eq!(get_storage_fee(storage_fee, creation_epoch, epoch, amount), 0);
+        assert_eq!(get_storage_fee(1, 2, 1, 100), 1);
+        assert_eq!(get_storage_fee(0, 100, 1, amount), 0);
+        assert_eq!(get_storage_fee(10_000, 2, 1, amount), amount);
+        assert_eq!(get_storage_fee(u16::MAX, 2, 1, amount), amount);
+    }
+
+    #[test]
+    fn test_storage_fee_matches_iterative() {
+        for ((amount, fee_bps), epochs) in samples(300).zip(samples(300).skip(1)).zip(samples(300).skip(2)) {
+            // amounts up to 1B SOL
+            for amount in [
+                amount % 1_000_000_000_000_000_000,
+                amount % 1_000_000_000_000,
+                amount % 1000,
+            ] {
+                let fee_bps = (fee_bps % 1000) as u16;
+                let epochs = epochs % 1000;
+
+                let fee = get_storage_fee(fee_bps, epochs, 0, amount);
+                let iterative_fee = get_iterative_storage_fee(fee_bps, epochs, 0, amount);
+
+                assert!(fee <= amount);
+                assert!(fee.abs_diff(iterative_fee) <= epochs.max(1));
+            }
+        }
+    }
+
+    #[test]
+    fn test_storage_fee_for_long_period() {
+        let amount = 1_000_000_000_000;
+
+        // ~50 years of epochs
+        let fee = get_storage_fee(1, 10_000, 0, amount);
+        let iterative_fee = get_iterative_storage_fee(1, 10_000, 0, amount);
+        assert!(fee.abs_diff(iterative_fee) <= 10_000);
+
+        assert_eq!(get_storage_fee(1, u64::MAX, 0, amount), amount);
+    }
+
+    #[test]
+    fn test_split_fee() {
+        let recipient = |share| FeeRecipient {
+            wallet: Pubkey::new_unique(),
+            share,
+        };
+
+        assert_eq!(split_fee(1_000, &[recipient(2_500), recipient(1_000)]), vec![250, 100]);
+        assert_eq!(split_fee(9, &[recipient(5_000), recipient(5_000)]), vec![4, 4]);
+        assert!(split_fee(1_000, &[]).is_empty());
+
+        for (amount, share) in samples(1_000).zip(samples(1_000).skip(1)) {
+            let recipients = [recipient((share % 5_001) as u16), recipient((share % 4_999) as u16)];
+            let total: u64 = split_fee(amount, &recipients).iter().sum();
+            assert!(total <= amount);
+        }
+    }
+
+    #[test]
+    fn test_is_payable() {
+        let rent = Rent::default();
+        let minimum = rent.minimum_balance(0);
+
+        assert!(is_payable(&rent, 0, 0, minimum));
+        assert!(!is_payable(&rent, 0, 0, minimum - 1));
+        assert!(is_payable(&rent, minimum, 0, 1));
+        assert!(is_payable(&rent, minimum - 1, 0, 1));
+        assert!(!is_payable(&rent, minimum, 0, 0));
+    }
+
+    #[test]
+    fn test_check_fee_recipients() {
+        let recipient = |share| FeeRecipient {
+            wallet: Pubkey::new_unique(),
+            share,
+        };
+
+        assert!(check_fee_recipients(&[]).is_ok());
+        assert!(check_fee_recipients(&[recipient(5_000), recipient(5_000)]).is_ok());
+        assert!(check_fee_recipients(&[recipient(5_000), recipient(5_001)]).is_err());
+        assert!(check_fee_recipients(&vec![recipient(1); FeeRecipient::MAX_RECIPIENTS + 1]).is_err());
+    }
+
+    #[test]
+    fn test_check_fee() {
+        assert!(check_fee(0).is_ok());
+        assert!(check_fee(10_000).is_ok());
+        assert!(check_fee(10_001).is_err());
     }
 }
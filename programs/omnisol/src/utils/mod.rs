@@ -1,6 +1,9 @@
+pub mod collateral;
 pub mod common;
 pub mod fee;
 pub mod stake;
+pub mod stake_pool;
 pub mod unstake_it;
+pub mod validator;
 
 pub use common::*;
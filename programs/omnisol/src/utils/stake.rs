@@ -5,12 +5,15 @@ use anchor_lang::{
     solana_program::{
         self,
         account_info::AccountInfo,
+        clock::Epoch,
         pubkey::Pubkey,
         stake::{
             self,
             program::ID,
-            state::{StakeAuthorize, StakeState},
+            state::{Delegation, StakeAuthorize, StakeState},
+            MINIMUM_STAKE_DELEGATION,
         },
+        stake_history::StakeHistory,
     },
     Accounts, AnchorDeserialize, Result, ToAccountInfo,
 };
@@ -98,6 +101,96 @@ pub fn merge<'info>(ctx: CpiContext<'_, '_, '_, 'info, Merge<'info>>) -> Result<
     .map_err(|error| error.into())
 }
 
+pub fn deactivate<'info>(ctx: CpiContext<'_, '_, '_, 'info, Deactivate<'info>>) -> Result<()> {
+    let ix = stake::instruction::deactivate_stake(ctx.accounts.stake.key, ctx.accounts.authority.key);
+    solana_program::program::invoke_signed(
+        &ix,
+        &[ctx.accounts.stake, ctx.accounts.clock, ctx.accounts.authority],
+        ctx.signer_seeds,
+    )
+    .map_err(|error| error.into())
+}
+
+pub fn delegate<'info>(ctx: CpiContext<'_, '_, '_, 'info, Delegate<'info>>) -> Result<()> {
+    let ix = stake::instruction::delegate_stake(
+        ctx.accounts.stake.key,
+        ctx.accounts.authority.key,
+        ctx.accounts.vote_account.key,
+    );
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.stake,
+            ctx.accounts.vote_account,
+            ctx.accounts.clock,
+            ctx.accounts.stake_history,
+            ctx.accounts.stake_config,
+            ctx.accounts.authority,
+        ],
+        ctx.signer_seeds,
+    )
+    .map_err(|error| error.into())
+}
+
+pub fn withdraw<'info>(ctx: CpiContext<'_, '_, '_, 'info, Withdraw<'info>>, lamports: u64) -> Result<()> {
+    let ix = stake::instruction::withdraw(
+        ctx.accounts.stake.key,
+        ctx.accounts.authority.key,
+        ctx.accounts.to.key,
+        lamports,
+        None,
+    );
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            ctx.accounts.stake,
+            ctx.accounts.to,
+            ctx.accounts.clock,
+            ctx.accounts.stake_history,
+            ctx.accounts.authority,
+        ],
+        ctx.signer_seeds,
+    )
+    .map_err(|error| error.into())
+}
+
+// Helpers
+
+/// Checks that the whole delegation is effective in the [epoch] and isn't being deactivated.
+pub fn is_fully_active(delegation: &Delegation, epoch: Epoch, stake_history: &StakeHistory) -> bool {
+    if delegation.deactivation_epoch != Epoch::MAX {
+        return false;
+    }
+
+    let status = delegation.stake_activating_and_deactivating(epoch, Some(stake_history));
+
+    status.activating == 0 && status.effective == delegation.stake
+}
+
+/// Checks that the delegation has been deactivated and none of its stake is effective in the [epoch].
+pub fn is_inactive(delegation: &Delegation, epoch: Epoch, stake_history: &StakeHistory) -> bool {
+    if delegation.deactivation_epoch == Epoch::MAX {
+        return false;
+    }
+
+    let status = delegation.stake_activating_and_deactivating(epoch, Some(stake_history));
+
+    status.effective == 0 && status.activating == 0 && status.deactivating == 0
+}
+
+/// Returns the lamports to split from the stake account, that delegates [stake] lamports,
+/// to a new account that delegates [amount] of it. The rent-exempt reserve of the new account
+/// is taken from the delegation, so None is returned if either account would delegate less than the minimum.
+pub fn get_split_lamports(amount: u64, stake: u64, rent_exempt_reserve: u64) -> Option<u64> {
+    if amount < MINIMUM_STAKE_DELEGATION
+        || stake.saturating_sub(amount) < rent_exempt_reserve.saturating_add(MINIMUM_STAKE_DELEGATION)
+    {
+        return None;
+    }
+
+    amount.checked_add(rent_exempt_reserve)
+}
+
 // CPI accounts
 
 #[derive(Accounts)]
@@ -112,6 +205,46 @@ pub struct Authorize<'info> {
     pub clock: AccountInfo<'info>,
 }
 
+#[derive(Accounts)]
+pub struct Deactivate<'info> {
+    /// CHECK:
+    pub stake: AccountInfo<'info>,
+    /// CHECK:
+    pub authority: AccountInfo<'info>,
+    /// CHECK:
+    pub clock: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Delegate<'info> {
+    /// CHECK:
+    pub stake: AccountInfo<'info>,
+    /// CHECK:
+    pub vote_account: AccountInfo<'info>,
+    /// CHECK:
+    pub authority: AccountInfo<'info>,
+    /// CHECK:
+    pub clock: AccountInfo<'info>,
+    /// CHECK:
+    pub stake_history: AccountInfo<'info>,
+    /// CHECK:
+    pub stake_config: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    /// CHECK:
+    pub stake: AccountInfo<'info>,
+    /// CHECK:
+    pub to: AccountInfo<'info>,
+    /// CHECK:
+    pub authority: AccountInfo<'info>,
+    /// CHECK:
+    pub clock: AccountInfo<'info>,
+    /// CHECK:
+    pub stake_history: AccountInfo<'info>,
+}
+
 #[derive(Accounts)]
 pub struct Split<'info> {
     /// CHECK:
@@ -179,3 +312,91 @@ impl anchor_lang::Id for Stake {
         ID
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use anchor_lang::solana_program::stake_history::StakeHistoryEntry;
+
+    use super::*;
+
+    #[test]
+    fn test_is_fully_active() {
+        let mut stake_history = StakeHistory::default();
+        // the stake is activating in epoch 10 and can become fully effective in epoch 11
+        stake_history.add(
+            10,
+            StakeHistoryEntry {
+                effective: 1_000_000_000_000,
+                activating: 1_000,
+                deactivating: 0,
+            },
+        );
+
+        let mut delegation = Delegation {
+            voter_pubkey: Pubkey::new_unique(),
+            stake: 1_000,
+            activation_epoch: 10,
+            ..Delegation::default()
+        };
+
+        assert!(!is_fully_active(&delegation, 10, &stake_history));
+        assert!(is_fully_active(&delegation, 11, &stake_history));
+
+        delegation.deactivation_epoch = 12;
+        assert!(!is_fully_active(&delegation, 11, &stake_history));
+        assert!(!is_fully_active(&delegation, 13, &stake_history));
+    }
+
+    #[test]
+    fn test_is_inactive() {
+        let mut stake_history = StakeHistory::default();
+        // the stake is deactivating in epoch 12 and can become inactive in epoch 13
+        stake_history.add(
+            12,
+            StakeHistoryEntry {
+                effective: 1_000_000_000_000,
+                activating: 0,
+                deactivating: 1_000,
+            },
+        );
+
+        let mut delegation = Delegation {
+            voter_pubkey: Pubkey::new_unique(),
+            stake: 1_000,
+            activation_epoch: 0,
+            ..Delegation::default()
+        };
+
+        assert!(!is_inactive(&delegation, 13, &stake_history));
+
+        delegation.deactivation_epoch = 12;
+        assert!(!is_inactive(&delegation, 12, &stake_history));
+        assert!(is_inactive(&delegation, 13, &stake_history));
+    }
+
+    #[test]
+    fn test_get_split_lamports() {
+        let rent_exempt_reserve = 2_282_880;
+
+        // partial deposit of 2 SOL leaves the rest delegated in the source account
+        assert_eq!(
+            get_split_lamports(2_000_000_000, 5_000_000_000, rent_exempt_reserve),
+            Some(2_000_000_000 + rent_exempt_reserve)
+        );
+        assert_eq!(
+            get_split_lamports(
+                5_000_000_000 - rent_exempt_reserve - 1,
+                5_000_000_000,
+                rent_exempt_reserve
+            ),
+            Some(5_000_000_000 - 1)
+        );
+
+        // the source account can't be left without delegated stake
+        assert_eq!(
+            get_split_lamports(5_000_000_000 - rent_exempt_reserve, 5_000_000_000, rent_exempt_reserve),
+            None
+        );
+        assert_eq!(get_split_lamports(0, 5_000_000_000, rent_exempt_reserve), None);
+    }
+}
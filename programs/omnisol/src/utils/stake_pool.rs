@@ -0,0 +1,88 @@
+use anchor_lang::{prelude::*, solana_program::borsh::try_from_slice_unchecked};
+use spl_stake_pool::state::StakePool;
+
+use crate::{state::Whitelist, ErrorCode};
+
+/// Reads the SPL stake pool of the whitelisted LP token.
+pub fn get_stake_pool(stake_pool: &AccountInfo, whitelist: &Whitelist) -> Result<StakePool> {
+    if stake_pool.key() != whitelist.pool || *stake_pool.owner != whitelist.pool_program {
+        msg!("Stake pool doesn't match the whitelist");
+        return Err(ErrorCode::InvalidStakePool.into());
+    }
+
+    let stake_pool = try_from_slice_unchecked::<StakePool>(&stake_pool.try_borrow_data()?)
+        .map_err(|_| ErrorCode::InvalidStakePool)?;
+
+    if !stake_pool.is_valid() || stake_pool.pool_mint != whitelist.mint {
+        msg!("Invalid stake pool state");
+        return Err(ErrorCode::InvalidStakePool.into());
+    }
+
+    Ok(stake_pool)
+}
+
+/// Values [pool_tokens] in lamports by the stake pool exchange rate, rounding down.
+pub fn get_lamports_value(stake_pool: &StakePool, pool_tokens: u64) -> Result<u64> {
+    if stake_pool.pool_token_supply == 0 {
+        return Err(ErrorCode::InvalidStakePool.into());
+    }
+
+    let lamports = (pool_tokens as u128)
+        .checked_mul(stake_pool.total_lamports as u128)
+        .ok_or(ErrorCode::TypeOverflow)?
+        / stake_pool.pool_token_supply as u128;
+
+    u64::try_from(lamports).map_err(|_| ErrorCode::TypeOverflow.into())
+}
+
+/// Converts [lamports] to pool tokens by the stake pool exchange rate, rounding up.
+pub fn get_pool_tokens(stake_pool: &StakePool, lamports: u64) -> Result<u64> {
+    if stake_pool.total_lamports == 0 {
+        return Err(ErrorCode::InvalidStakePool.into());
+    }
+
+    let total_lamports = stake_pool.total_lamports as u128;
+    let pool_tokens = (lamports as u128)
+        .checked_mul(stake_pool.pool_token_supply as u128)
+        .ok_or(ErrorCode::TypeOverflow)?
+        .checked_add(total_lamports - 1)
+        .ok_or(ErrorCode::TypeOverflow)?
+        / total_lamports;
+
+    u64::try_from(pool_tokens).map_err(|_| ErrorCode::TypeOverflow.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get_lamports_value() {
+        let stake_pool = StakePool {
+            total_lamports: 1_050_000_000,
+            pool_token_supply: 1_000_000_000,
+            ..StakePool::default()
+        };
+
+        assert_eq!(get_lamports_value(&stake_pool, 1_000_000_000).unwrap(), 1_050_000_000);
+        // rounds down in favor of the protocol
+        assert_eq!(get_lamports_value(&stake_pool, 19).unwrap(), 19);
+        assert!(get_lamports_value(&StakePool::default(), 1).is_err());
+    }
+
+    #[test]
+    fn test_get_pool_tokens() {
+        let stake_pool = StakePool {
+            total_lamports: 1_050_000_000,
+            pool_token_supply: 1_000_000_000,
+            ..StakePool::default()
+        };
+
+        assert_eq!(get_pool_tokens(&stake_pool, 1_050_000_000).unwrap(), 1_000_000_000);
+        // rounds up, so the pool tokens are worth at least the lamports
+        assert_eq!(get_pool_tokens(&stake_pool, 20).unwrap(), 20);
+        assert!(get_lamports_value(&stake_pool, get_pool_tokens(&stake_pool, 20).unwrap()).unwrap() >= 20);
+        assert_eq!(get_pool_tokens(&stake_pool, 0).unwrap(), 0);
+        assert!(get_pool_tokens(&StakePool::default(), 1).is_err());
+    }
+}
@@ -0,0 +1,13 @@
+use anchor_lang::prelude::*;
+
+use crate::state::Validator;
+
+/// Loads the validator entry of the pool, if it's initialized.
+/// The address of the entry has to be checked by the caller.
+pub fn load_validator<'info>(info: &AccountInfo<'info>) -> Result<Option<Account<'info, Validator>>> {
+    if info.owner != &crate::ID {
+        return Ok(None);
+    }
+
+    Ok(Some(Account::try_from(info)?))
+}